@@ -0,0 +1,166 @@
+use rocket::serde::json::serde_json;
+use rocket::serde::uuid::Uuid;
+use rocket::serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::config_dir;
+use crate::untis::UsernamePassword;
+
+/// What an access token was handed out for. Tokens are only accepted where they came from, so a
+/// dashboard cookie cannot be used as voice assistant token and vice versa.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(crate = "rocket::serde", rename_all = "camelCase")]
+pub(crate) enum TokenOrigin {
+    /// Account linking of a voice assistant (also tokens stored before origins were recorded)
+    #[default]
+    Assistant,
+    /// Login to the dashboard
+    Dashboard,
+    /// Exam calendar subscription, the token is part of the subscribed URL
+    Calendar,
+}
+
+/// Days a dashboard login lasts, independent of `UNTIS_TOKEN_DAYS`
+pub(crate) const DASHBOARD_SESSION_DAYS: i64 = 30;
+
+impl TokenOrigin {
+    pub(crate) fn lifetime(&self) -> chrono::TimeDelta {
+        match self {
+            TokenOrigin::Assistant | TokenOrigin::Calendar => token_lifetime(),
+            TokenOrigin::Dashboard => chrono::TimeDelta::days(DASHBOARD_SESSION_DAYS),
+        }
+    }
+}
+
+/// Credentials stored for an access token
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct LinkedAccount {
+    #[serde(flatten)]
+    pub(crate) user: UsernamePassword,
+    #[serde(default)]
+    origin: TokenOrigin,
+    /// When the access token was handed out. Accounts linked before tokens expired count from
+    /// the first start with expiry.
+    #[serde(default = "chrono::Utc::now")]
+    created: chrono::DateTime<chrono::Utc>,
+}
+
+impl LinkedAccount {
+    fn expired(&self) -> bool {
+        chrono::Utc::now() - self.created >= self.origin.lifetime()
+    }
+}
+
+/// Days an access token of a linked voice assistant stays valid, from `UNTIS_TOKEN_DAYS`
+/// (default: 180)
+fn token_lifetime() -> chrono::TimeDelta {
+    chrono::TimeDelta::days(
+        std::env::var("UNTIS_TOKEN_DAYS")
+            .ok()
+            .and_then(|days| days.trim().parse().ok())
+            .unwrap_or(180),
+    )
+}
+
+/// WebUntis credentials of voice assistant users, by the access token handed out during account
+/// linking. Persisted in `<config dir>/accounts.json`, readable only by the owner.
+pub(crate) struct AccountStore {
+    pub(crate) path: PathBuf,
+    pub(crate) accounts: Mutex<HashMap<String, LinkedAccount>>,
+}
+
+impl AccountStore {
+    pub(crate) fn load() -> anyhow::Result<Self> {
+        let path = config_dir().join("accounts.json");
+        let accounts = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?
+        } else {
+            HashMap::new()
+        };
+        let store = AccountStore {
+            path,
+            accounts: Mutex::new(accounts),
+        };
+        // Drops expired tokens and restricts the permissions of files written before
+        let accounts = store.accounts.lock().unwrap().clone();
+        if !accounts.is_empty() {
+            store.save(&accounts)?;
+        }
+        Ok(store)
+    }
+
+    /// Credentials of the access token, unless it is unknown, expired or was handed out for
+    /// something else than `origin`
+    pub(crate) fn get(&self, access_token: &str, origin: TokenOrigin) -> Option<UsernamePassword> {
+        self.accounts
+            .lock()
+            .unwrap()
+            .get(access_token)
+            .filter(|account| account.origin == origin && !account.expired())
+            .map(|account| account.user.clone())
+    }
+
+    /// Stores the credentials and returns a new access token for them
+    pub(crate) fn link(
+        &self,
+        user: UsernamePassword,
+        origin: TokenOrigin,
+    ) -> anyhow::Result<String> {
+        let token = Uuid::new_v4().simple().to_string();
+        let mut accounts = self.accounts.lock().unwrap();
+        accounts.insert(
+            token.to_string(),
+            LinkedAccount {
+                user,
+                origin,
+                created: chrono::Utc::now(),
+            },
+        );
+        self.save(&accounts)?;
+        Ok(token)
+    }
+
+    /// Forgets the credentials of the access token, if it was handed out for `origin`. Returns
+    /// whether there was such a token.
+    pub(crate) fn unlink(&self, access_token: &str, origin: TokenOrigin) -> anyhow::Result<bool> {
+        let mut accounts = self.accounts.lock().unwrap();
+        if accounts
+            .get(access_token)
+            .is_none_or(|account| account.origin != origin)
+        {
+            return Ok(false);
+        }
+        accounts.remove(access_token);
+        self.save(&accounts)?;
+        Ok(true)
+    }
+
+    /// Writes all unexpired accounts to a new file only the owner can read, which then replaces
+    /// the old one, so that a crash never leaves a truncated file behind
+    pub(crate) fn save(&self, accounts: &HashMap<String, LinkedAccount>) -> anyhow::Result<()> {
+        use std::io::Write;
+
+        let accounts: HashMap<_, _> = accounts
+            .iter()
+            .filter(|(_, account)| !account.expired())
+            .collect();
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let temp_path = self.path.with_extension("json.tmp");
+        // The mode only applies to new files
+        let _ = std::fs::remove_file(&temp_path);
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&temp_path)?;
+        file.write_all(serde_json::to_string_pretty(&accounts)?.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+}
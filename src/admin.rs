@@ -0,0 +1,86 @@
+use rocket::http::Status;
+use rocket::request::{FromRequest, Request};
+use rocket::serde::json::Json;
+use rocket::serde::Deserialize;
+use rocket::{request, State};
+use std::collections::BTreeMap;
+
+use crate::speech::Lexicon;
+
+/// Compares a secret sent by a client with the expected one in constant time. Both are hashed
+/// first, so that neither their content nor their length can be timed.
+pub(crate) fn secret_matches(given: &str, expected: &str) -> bool {
+    openssl::memcmp::eq(
+        &openssl::sha::sha256(given.as_bytes()),
+        &openssl::sha::sha256(expected.as_bytes()),
+    )
+}
+
+/// Requests authorized with `Authorization: Bearer <UNTIS_ADMIN_TOKEN>`. The admin API is
+/// disabled, if no token is configured.
+pub(crate) struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let Ok(token) = std::env::var("UNTIS_ADMIN_TOKEN") else {
+            return request::Outcome::Error((Status::NotFound, ()));
+        };
+        let authorization = request.headers().get_one("Authorization");
+        match authorization.and_then(|value| value.strip_prefix("Bearer ")) {
+            Some(bearer) if !token.is_empty() && secret_matches(bearer, &token) => {
+                request::Outcome::Success(Admin)
+            }
+            _ => {
+                warn!("Rejecting admin request with wrong authorization");
+                request::Outcome::Error((Status::Unauthorized, ()))
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct LexiconEntry {
+    pub(crate) spoken: String,
+}
+
+/// All entries of the pronunciation lexicon, by written name
+#[get("/admin/lexicon")]
+pub(crate) fn lexicon_entries(
+    _admin: Admin,
+    lexicon: &State<Lexicon>,
+) -> Json<BTreeMap<String, String>> {
+    Json(lexicon.entries())
+}
+
+/// Adds or replaces the spoken form of a name
+#[put("/admin/lexicon/<name>", data = "<entry>")]
+pub(crate) fn set_lexicon_entry(
+    _admin: Admin,
+    name: &str,
+    entry: Json<LexiconEntry>,
+    lexicon: &State<Lexicon>,
+) -> Status {
+    match lexicon.insert(name, &entry.spoken) {
+        Ok(()) => Status::NoContent,
+        Err(err) => {
+            error!("Could not save pronunciation lexicon: {err:#}");
+            Status::InternalServerError
+        }
+    }
+}
+
+#[delete("/admin/lexicon/<name>")]
+pub(crate) fn delete_lexicon_entry(_admin: Admin, name: &str, lexicon: &State<Lexicon>) -> Status {
+    match lexicon.remove(name) {
+        Ok(true) => Status::NoContent,
+        Ok(false) => Status::NotFound,
+        Err(err) => {
+            error!("Could not save pronunciation lexicon: {err:#}");
+            Status::InternalServerError
+        }
+    }
+}
@@ -0,0 +1,781 @@
+use rocket::http::{ContentType, Status};
+use rocket::serde::json::Json;
+use rocket::serde::Serialize;
+use rocket::State;
+
+use crate::accounts::{AccountStore, TokenOrigin};
+use crate::language::Language;
+use crate::speech::templates::SpeechTemplates;
+use crate::speech::tts::TextToSpeech;
+use crate::speech::{
+    speakable_day, speakable_day_name, speakable_holiday, DayOverview, Lexicon, Speech,
+    SpeechFormat,
+};
+use crate::untis::absences::{absences_csv, fetch_absences, Absence};
+use crate::untis::availability::{
+    find_free_rooms, find_free_teachers, parse_period_numbers, FreeRooms, FreeTeachers,
+    RequestedTime, TimetablesUnreadable,
+};
+use crate::untis::calendar::{school_calendar, SchoolCalendar};
+use crate::untis::exams::{exams_ics, fetch_exams, Exam};
+use crate::untis::homework::{fetch_homework, DateOutOfRange, Homework};
+use crate::untis::messages::{
+    fetch_day_messages, fetch_inbox, fetch_message, DayMessage, Inbox, Message, MessageAttachment,
+};
+use crate::untis::substitutions::{get_substitutions, Substitution};
+use crate::untis::timetable::{
+    fetch_timetable, merged_timetable, parse_mode, ElementType, ParseMode, ParseWarning, Period,
+};
+use crate::untis::{build_client, login, logout, upstream_status, UsernamePassword};
+
+fn requested_element<'a>(
+    class: Option<&'a str>,
+    teacher: Option<&'a str>,
+    room: Option<&'a str>,
+) -> Option<(ElementType, &'a str)> {
+    match (class, teacher, room) {
+        (Some(name), _, _) => Some((ElementType::Klasse, name)),
+        (None, Some(name), _) => Some((ElementType::Teacher, name)),
+        (None, None, Some(name)) => Some((ElementType::Room, name)),
+        (None, None, None) => None,
+    }
+}
+
+#[post("/timetable?<class>&<teacher>&<room>", data = "<user>")]
+pub(crate) async fn timetable(
+    class: Option<&str>,
+    teacher: Option<&str>,
+    room: Option<&str>,
+    user: Json<UsernamePassword>,
+) -> Result<Json<Vec<Period>>, Status> {
+    match fetch_timetable(
+        &user,
+        requested_element(class, teacher, room),
+        chrono::Local::now().date_naive(),
+        parse_mode(),
+    )
+    .await
+    {
+        Ok(Some((timetable, _))) => Ok(Json(timetable)),
+        // The requested class, teacher or room does not exist
+        Ok(None) => Err(Status::NotFound),
+        Err(err) => {
+            warn!("Could not retrieve timetable: {err:#}");
+            Err(upstream_status(&err))
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct ParseReport {
+    pub(crate) periods: Vec<Period>,
+    pub(crate) warnings: Vec<ParseWarning>,
+    /// Why the timetable could not be retrieved or parsed at all
+    pub(crate) error: Option<String>,
+}
+
+/// Parses the timetable leniently and returns all problems encountered along with the periods.
+/// If the timetable cannot be retrieved or parsed at all, the error is reported instead, with
+/// the status `/timetable` would answer with.
+#[post("/debug/timetable?<class>&<teacher>&<room>", data = "<user>")]
+pub(crate) async fn debug_timetable(
+    class: Option<&str>,
+    teacher: Option<&str>,
+    room: Option<&str>,
+    user: Json<UsernamePassword>,
+) -> (Status, Json<ParseReport>) {
+    let element = requested_element(class, teacher, room);
+    let failed = |status, error: String| {
+        (
+            status,
+            Json(ParseReport {
+                periods: vec![],
+                warnings: vec![],
+                error: Some(error),
+            }),
+        )
+    };
+    match fetch_timetable(
+        &user,
+        element,
+        chrono::Local::now().date_naive(),
+        ParseMode::Lenient,
+    )
+    .await
+    {
+        Ok(Some((periods, warnings))) => (
+            Status::Ok,
+            Json(ParseReport {
+                periods,
+                warnings,
+                error: None,
+            }),
+        ),
+        Ok(None) => failed(
+            Status::NotFound,
+            element
+                .map(|(element_type, name)| format!("{element_type:?} '{name}' not found"))
+                .unwrap_or_default(),
+        ),
+        Err(err) => {
+            warn!("Could not retrieve timetable: {err:#}");
+            failed(upstream_status(&err), format!("{err:#}"))
+        }
+    }
+}
+
+/// Query parameters shared by the speakable endpoints
+#[derive(FromForm)]
+pub(crate) struct SpeakableOptions {
+    /// "in der 3. Stunde" instead of "zwischen 09:45 und 10:30 Uhr"
+    pub(crate) period_numbers: Option<bool>,
+    /// The day's key figures instead of every change
+    pub(crate) summary: Option<bool>,
+    /// Precede the output with the messages of the day
+    pub(crate) news: Option<bool>,
+}
+
+#[post("/speakable?<format>&<options..>", data = "<user>")]
+pub(crate) async fn speakable(
+    format: Option<&str>,
+    options: SpeakableOptions,
+    user: Json<UsernamePassword>,
+    language: Language,
+    templates: &State<SpeechTemplates>,
+    lexicon: &State<Lexicon>,
+) -> Result<(ContentType, String), Status> {
+    let speech = Speech {
+        language,
+        format: SpeechFormat::from_name(format),
+        period_numbers: options.period_numbers.unwrap_or(false),
+        lexicon,
+        account: &user.username,
+    };
+    let text = speakable_today(
+        &user,
+        speech,
+        options.summary.unwrap_or(false),
+        options.news.unwrap_or(false),
+        templates,
+    )
+    .await?;
+    Ok((speech.content_type(), text))
+}
+
+/// The changes of today (or their summary, followed by the homework due on the next school day)
+/// as one speakable text, optionally preceded by the messages of the day. Days without school
+/// name the next school day, the last school day before a break names the break. Fails with the
+/// response status, if the timetable cannot be retrieved.
+async fn speakable_today(
+    user: &UsernamePassword,
+    speech: Speech<'_>,
+    summary: bool,
+    news: bool,
+    templates: &SpeechTemplates,
+) -> Result<String, Status> {
+    let today = chrono::Local::now().date_naive();
+    let mut sentences = vec![];
+    if news {
+        match fetch_day_messages(user, &[today]).await {
+            Ok(mut messages) => sentences.extend(
+                messages
+                    .remove(&today)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|message| message.speakable(speech)),
+            ),
+            Err(err) => warn!("Could not retrieve messages of the day: {err:#}"),
+        }
+    }
+    // Retrieved first, so that the calendar is only retrieved with credentials known to work
+    let (timetable, _) = fetch_timetable(user, None, today, parse_mode())
+        .await
+        .map_err(|err| {
+            warn!("Could not retrieve timetable: {err:#}");
+            upstream_status(&err)
+        })?
+        .ok_or(Status::NotFound)?;
+    let calendar = school_calendar(user).await;
+    let next_school_day = calendar.next_school_day(today);
+    if let Some(holiday) = calendar.holiday(today) {
+        sentences.push(speakable_holiday(speech, holiday, today, today));
+        return Ok(speech.join(sentences));
+    }
+    if !calendar.is_school_day(today) {
+        // Weekends and days outside of the school year
+        sentences.push(speech.translate(
+            "summary-no-lessons",
+            &[("day", &speakable_day_name(speech, today, today))],
+        ));
+        if let Some(next) = next_school_day {
+            sentences.push(speech.translate(
+                "school-resumes",
+                &[("day", &speakable_day_name(speech, next, today))],
+            ));
+        }
+        return Ok(speech.join(sentences));
+    }
+    let timetable = merged_timetable(timetable);
+    if summary {
+        sentences.push(DayOverview::new(&timetable, today).speakable(speech, today));
+    } else {
+        sentences.extend(speakable_day(&timetable, today, speech, templates));
+    }
+    if let Some(due) = next_school_day.filter(|_| summary) {
+        match fetch_homework(user, due, due).await {
+            Ok(homework) => sentences.extend(
+                homework
+                    .iter()
+                    .filter(|homework| !homework.completed)
+                    .map(|homework| homework.speakable(speech, today)),
+            ),
+            Err(err) => warn!("Could not retrieve homework: {err:#}"),
+        }
+    }
+    // On the last school day before a break
+    if let Some(holiday) = calendar.holidays.iter().find(|holiday| {
+        holiday.start > today && next_school_day.is_none_or(|next| holiday.start < next)
+    }) {
+        sentences.push(speakable_holiday(speech, holiday, holiday.start, today));
+    }
+    Ok(speech.join(sentences))
+}
+
+/// The speakable changes of today as audio, for devices that cannot do text-to-speech themselves
+#[post("/speakable/audio?<options..>", data = "<user>")]
+pub(crate) async fn speakable_audio(
+    options: SpeakableOptions,
+    user: Json<UsernamePassword>,
+    language: Language,
+    templates: &State<SpeechTemplates>,
+    lexicon: &State<Lexicon>,
+    tts: &State<Option<TextToSpeech>>,
+) -> Result<(ContentType, Vec<u8>), Status> {
+    let Some(tts) = tts.inner() else {
+        return Err(Status::NotFound);
+    };
+    let speech = Speech {
+        language,
+        format: tts.input,
+        period_numbers: options.period_numbers.unwrap_or(false),
+        lexicon,
+        account: &user.username,
+    };
+    let text = speakable_today(
+        &user,
+        speech,
+        options.summary.unwrap_or(false),
+        options.news.unwrap_or(false),
+        templates,
+    )
+    .await?;
+    // Engines fail on empty input, so a day without anything to say gets a sentence of its own
+    let text = if text.is_empty() || text == speech.join(vec![]) {
+        let today = chrono::Local::now().date_naive();
+        speech.join(vec![speech.translate(
+            "assistant-no-changes",
+            &[("day", &speakable_day_name(speech, today, today))],
+        )])
+    } else {
+        text
+    };
+    match tts.render(&text, language).await {
+        Ok(audio) => Ok((tts.format.content_type(), audio)),
+        Err(err) => {
+            error!("Could not synthesize speech: {err:#}");
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// Key figures of the day, by default today
+#[post("/overview?<date>", data = "<user>")]
+pub(crate) async fn overview(
+    date: Option<&str>,
+    user: Json<UsernamePassword>,
+) -> Result<Json<DayOverview>, Status> {
+    let date = match date {
+        Some(date) => {
+            chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| Status::BadRequest)?
+        }
+        None => chrono::Local::now().date_naive(),
+    };
+    let (timetable, _) = fetch_timetable(&user, None, date, parse_mode())
+        .await
+        .map_err(|err| {
+            warn!("Could not retrieve timetable for the overview: {err:#}");
+            Status::BadGateway
+        })?
+        .ok_or(Status::NotFound)?;
+    Ok(Json(DayOverview::new(&timetable, date)))
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct CalendarReport {
+    #[serde(flatten)]
+    pub(crate) calendar: SchoolCalendar,
+    #[serde(rename = "nextSchoolDay")]
+    pub(crate) next_school_day: Option<chrono::NaiveDate>,
+}
+
+/// Holidays, school years and the next school day
+#[post("/calendar", data = "<user>")]
+pub(crate) async fn calendar(user: Json<UsernamePassword>) -> Json<CalendarReport> {
+    let calendar = school_calendar(&user).await;
+    Json(CalendarReport {
+        next_school_day: calendar.next_school_day(chrono::Local::now().date_naive()),
+        calendar: SchoolCalendar::clone(&calendar),
+    })
+}
+
+/// The date range of exam requests: from today, `days` days ahead (by default four weeks).
+/// `400 Bad Request`, if that is beyond the dates that can be represented.
+fn exam_range(days: Option<u64>) -> Result<(chrono::NaiveDate, chrono::NaiveDate), Status> {
+    let today = chrono::Local::now().date_naive();
+    let to = today
+        .checked_add_days(chrono::Days::new(days.unwrap_or(28)))
+        .ok_or(Status::BadRequest)?;
+    Ok((today, to))
+}
+
+/// Upcoming exams of the logged in student
+#[post("/exams?<days>", data = "<user>")]
+pub(crate) async fn exams(
+    days: Option<u64>,
+    user: Json<UsernamePassword>,
+) -> Result<Json<Vec<Exam>>, Status> {
+    let (from, to) = exam_range(days)?;
+    fetch_exams(&user, from, to).await.map(Json).map_err(|err| {
+        error!("Could not retrieve exams: {err:#}");
+        Status::BadGateway
+    })
+}
+
+#[post("/exams/speakable?<format>&<days>&<period_numbers>", data = "<user>")]
+pub(crate) async fn exams_speakable(
+    format: Option<&str>,
+    days: Option<u64>,
+    period_numbers: Option<bool>,
+    user: Json<UsernamePassword>,
+    language: Language,
+    lexicon: &State<Lexicon>,
+) -> Result<(ContentType, String), Status> {
+    let speech = Speech {
+        language,
+        format: SpeechFormat::from_name(format),
+        period_numbers: period_numbers.unwrap_or(true),
+        lexicon,
+        account: &user.username,
+    };
+    let (from, to) = exam_range(days)?;
+    let exams = fetch_exams(&user, from, to).await.map_err(|err| {
+        error!("Could not retrieve exams: {err:#}");
+        Status::BadGateway
+    })?;
+    let sentences = if exams.is_empty() {
+        vec![speech.translate(
+            "exams-none",
+            &[("days", &(to - from).num_days().to_string())],
+        )]
+    } else {
+        exams
+            .iter()
+            .map(|exam| exam.speakable(speech, from))
+            .collect()
+    };
+    Ok((speech.content_type(), speech.join(sentences)))
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct CalendarToken {
+    pub(crate) token: String,
+    pub(crate) url: String,
+}
+
+/// Hands out a token for the exam calendar subscription. It only grants access to the calendar
+/// and can be revoked on its own, as it ends up in URLs, logs and calendar apps.
+#[post("/exams/calendar", data = "<user>")]
+pub(crate) async fn exams_calendar_token(
+    user: Json<UsernamePassword>,
+    accounts: &State<AccountStore>,
+) -> Result<Json<CalendarToken>, Status> {
+    let client = build_client();
+    info!(
+        "Verifying credentials of {} for the exam calendar...",
+        user.username
+    );
+    let userinfo = login(&client, &user.username, &user.password)
+        .await
+        .map_err(|err| {
+            info!(
+                "Could not create calendar token for {}: {err}",
+                user.username
+            );
+            Status::Unauthorized
+        })?;
+    logout(&client, &userinfo.session_id)
+        .await
+        .map_err(|_| Status::BadGateway)?;
+
+    let token = accounts
+        .link(user.into_inner(), TokenOrigin::Calendar)
+        .map_err(|err| {
+            error!("Could not store calendar token: {err}");
+            Status::InternalServerError
+        })?;
+    Ok(Json(CalendarToken {
+        url: format!("/exams/calendar.ics?token={token}"),
+        token,
+    }))
+}
+
+/// Revokes a calendar token, the subscription stops working
+#[delete("/exams/calendar.ics?<token>")]
+pub(crate) fn revoke_exams_calendar_token(token: &str, accounts: &State<AccountStore>) -> Status {
+    match accounts.unlink(token, TokenOrigin::Calendar) {
+        Ok(true) => Status::NoContent,
+        Ok(false) => Status::NotFound,
+        Err(err) => {
+            error!("Could not remove calendar token: {err}");
+            Status::InternalServerError
+        }
+    }
+}
+
+/// Exams as calendar subscription. Calendar apps cannot send credentials, so a calendar token
+/// (see [`exams_calendar_token`]) is passed instead.
+#[get("/exams/calendar.ics?<token>&<days>")]
+pub(crate) async fn exams_calendar(
+    token: &str,
+    days: Option<u64>,
+    accounts: &State<AccountStore>,
+) -> Result<(ContentType, String), Status> {
+    let user = accounts
+        .get(token, TokenOrigin::Calendar)
+        .ok_or(Status::Unauthorized)?;
+    let (from, to) = exam_range(days)?;
+    let exams = fetch_exams(&user, from, to).await.map_err(|err| {
+        error!("Could not retrieve exams: {err:#}");
+        Status::BadGateway
+    })?;
+    Ok((ContentType::Calendar, exams_ics(&exams)))
+}
+
+/// Homework due from `from` (by default today) to `to` (by default a week later). `400 Bad
+/// Request`, if the range reaches beyond the dates that can be represented.
+#[post("/homework?<from>&<to>", data = "<user>")]
+pub(crate) async fn homework(
+    from: Option<&str>,
+    to: Option<&str>,
+    user: Json<UsernamePassword>,
+) -> Result<Json<Vec<Homework>>, Status> {
+    let parse_date = |date: &str| {
+        chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| Status::BadRequest)
+    };
+    let from = match from {
+        Some(from) => parse_date(from)?,
+        None => chrono::Local::now().date_naive(),
+    };
+    let to = match to {
+        Some(to) => parse_date(to)?,
+        None => from
+            .checked_add_days(chrono::Days::new(7))
+            .ok_or(Status::BadRequest)?,
+    };
+    if to < from {
+        return Err(Status::BadRequest);
+    }
+    fetch_homework(&user, from, to)
+        .await
+        .map(Json)
+        .map_err(|err| {
+            if err.is::<DateOutOfRange>() {
+                return Status::BadRequest;
+            }
+            error!("Could not retrieve homework: {err:#}");
+            Status::BadGateway
+        })
+}
+
+/// The date range of absence requests: from `from` (by default the start of the school year) to
+/// `to` (by default today). `400 Bad Request`, if that is beyond the dates that can be represented.
+async fn absence_range(
+    user: &UsernamePassword,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<(chrono::NaiveDate, chrono::NaiveDate), Status> {
+    let parse_date = |date: &str| {
+        chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| Status::BadRequest)
+    };
+    let today = chrono::Local::now().date_naive();
+    let to = to.map(parse_date).transpose()?.unwrap_or(today);
+    let from = match from {
+        Some(from) => parse_date(from)?,
+        None => match school_calendar(user)
+            .await
+            .school_years
+            .iter()
+            .find(|year| year.start <= to && to <= year.end)
+        {
+            Some(year) => year.start,
+            None => to
+                .checked_sub_days(chrono::Days::new(ABSENCE_DEFAULT_DAYS))
+                .ok_or(Status::BadRequest)?,
+        },
+    };
+    if to < from {
+        return Err(Status::BadRequest);
+    }
+    Ok((from, to))
+}
+
+/// How far back absences are listed, if the school year is unknown
+const ABSENCE_DEFAULT_DAYS: u64 = 90;
+
+/// Recorded absences of the logged in student
+#[post("/absences?<from>&<to>", data = "<user>")]
+pub(crate) async fn absences(
+    from: Option<&str>,
+    to: Option<&str>,
+    user: Json<UsernamePassword>,
+) -> Result<Json<Vec<Absence>>, Status> {
+    let (from, to) = absence_range(&user, from, to).await?;
+    fetch_absences(&user, from, to)
+        .await
+        .map(Json)
+        .map_err(|err| {
+            error!("Could not retrieve absences: {err:#}");
+            Status::BadGateway
+        })
+}
+
+/// Recorded absences of the logged in student as CSV, e.g. for spreadsheets
+#[post("/absences.csv?<from>&<to>", data = "<user>")]
+pub(crate) async fn absences_export(
+    from: Option<&str>,
+    to: Option<&str>,
+    user: Json<UsernamePassword>,
+) -> Result<(ContentType, String), Status> {
+    let (from, to) = absence_range(&user, from, to).await?;
+    let absences = fetch_absences(&user, from, to).await.map_err(|err| {
+        error!("Could not retrieve absences: {err:#}");
+        Status::BadGateway
+    })?;
+    Ok((ContentType::CSV, absences_csv(&absences)))
+}
+
+/// The messenger inbox of the logged in user with the number of unread messages
+#[post("/messages", data = "<user>")]
+pub(crate) async fn messages(user: Json<UsernamePassword>) -> Result<Json<Inbox>, Status> {
+    fetch_inbox(&user).await.map(Json).map_err(|err| {
+        error!("Could not retrieve messages: {err:#}");
+        Status::BadGateway
+    })
+}
+
+/// A message with its content. As WebUntis may mark the message as read, the caller has to agree
+/// to that with `mark_read=true`, otherwise `400 Bad Request`.
+#[post("/messages/<id>?<mark_read>", data = "<user>")]
+pub(crate) async fn message(
+    id: u64,
+    mark_read: Option<bool>,
+    user: Json<UsernamePassword>,
+) -> Result<Json<Message>, Status> {
+    if mark_read != Some(true) {
+        return Err(Status::BadRequest);
+    }
+    fetch_message(&user, id).await.map(Json).map_err(|err| {
+        error!("Could not retrieve message {id}: {err:#}");
+        Status::BadGateway
+    })
+}
+
+/// Metadata of the files attached to a message. They are only part of the full message, so this
+/// requires `mark_read=true` just like [`message`].
+#[post("/messages/<id>/attachments?<mark_read>", data = "<user>")]
+pub(crate) async fn message_attachments(
+    id: u64,
+    mark_read: Option<bool>,
+    user: Json<UsernamePassword>,
+) -> Result<Json<Vec<MessageAttachment>>, Status> {
+    if mark_read != Some(true) {
+        return Err(Status::BadRequest);
+    }
+    fetch_message(&user, id)
+        .await
+        .map(|message| Json(message.attachments))
+        .map_err(|err| {
+            error!("Could not retrieve message {id}: {err:#}");
+            Status::BadGateway
+        })
+}
+
+/// The messages of the day from the news widget, by default of today
+#[post("/news?<date>", data = "<user>")]
+pub(crate) async fn news(
+    date: Option<&str>,
+    user: Json<UsernamePassword>,
+) -> Result<Json<Vec<DayMessage>>, Status> {
+    let date = match date {
+        Some(date) => {
+            chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| Status::BadRequest)?
+        }
+        None => chrono::Local::now().date_naive(),
+    };
+    fetch_day_messages(&user, &[date])
+        .await
+        .map(|mut messages| Json(messages.remove(&date).unwrap_or_default()))
+        .map_err(|err| {
+            error!("Could not retrieve messages of the day: {err:#}");
+            Status::BadGateway
+        })
+}
+
+/// Rooms that are free in `period` (e.g. `3` or `3-4`) on `date` (by default today), optionally
+/// only those with room for at least `capacity` people
+#[post("/rooms/free?<date>&<period>&<capacity>", data = "<user>")]
+pub(crate) async fn free_rooms(
+    date: Option<&str>,
+    period: Option<&str>,
+    capacity: Option<u64>,
+    user: Json<UsernamePassword>,
+) -> Result<Json<FreeRooms>, Status> {
+    let date = match date {
+        Some(date) => {
+            chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| Status::BadRequest)?
+        }
+        None => chrono::Local::now().date_naive(),
+    };
+    let numbers = period
+        .and_then(parse_period_numbers)
+        .ok_or(Status::BadRequest)?;
+    match find_free_rooms(&user, date, numbers, capacity).await {
+        Ok(Some(rooms)) => Ok(Json(rooms)),
+        // No such periods on that day
+        Ok(None) => Err(Status::NotFound),
+        Err(err) if err.is::<TimetablesUnreadable>() => {
+            warn!("Could not look for free rooms: {err}");
+            Err(Status::Forbidden)
+        }
+        Err(err) => {
+            error!("Could not look for free rooms: {err:#}");
+            Err(Status::BadGateway)
+        }
+    }
+}
+
+/// Teachers without lessons on `date` (by default today), either in `period` (e.g. `3` or
+/// `3-4`) or from `start` to `end` (e.g. `13:30` and `15:00`)
+#[post("/teachers/free?<date>&<period>&<start>&<end>", data = "<user>")]
+pub(crate) async fn free_teachers(
+    date: Option<&str>,
+    period: Option<&str>,
+    start: Option<&str>,
+    end: Option<&str>,
+    user: Json<UsernamePassword>,
+) -> Result<Json<FreeTeachers>, Status> {
+    let date = match date {
+        Some(date) => {
+            chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| Status::BadRequest)?
+        }
+        None => chrono::Local::now().date_naive(),
+    };
+    let parse_time = |time: &str| {
+        chrono::NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| Status::BadRequest)
+    };
+    let time = match (period, start, end) {
+        (Some(period), None, None) => {
+            RequestedTime::Periods(parse_period_numbers(period).ok_or(Status::BadRequest)?)
+        }
+        (None, Some(start), Some(end)) => {
+            let (start, end) = (parse_time(start)?, parse_time(end)?);
+            if end <= start {
+                return Err(Status::BadRequest);
+            }
+            RequestedTime::Range(start, end)
+        }
+        _ => return Err(Status::BadRequest),
+    };
+    match find_free_teachers(&user, date, time).await {
+        Ok(Some(teachers)) => Ok(Json(teachers)),
+        // No such periods on that day
+        Ok(None) => Err(Status::NotFound),
+        Err(err) if err.is::<TimetablesUnreadable>() => {
+            warn!("Could not look for free teachers: {err}");
+            Err(Status::Forbidden)
+        }
+        Err(err) => {
+            error!("Could not look for free teachers: {err:#}");
+            Err(Status::BadGateway)
+        }
+    }
+}
+
+/// The school-wide substitution plan from `from` to `to` (by default today), optionally only
+/// the changes of one class or teacher
+#[post("/substitutions?<class>&<teacher>&<from>&<to>", data = "<user>")]
+pub(crate) async fn substitutions(
+    class: Option<&str>,
+    teacher: Option<&str>,
+    from: Option<&str>,
+    to: Option<&str>,
+    user: Json<UsernamePassword>,
+) -> Result<Json<Vec<Substitution>>, Status> {
+    let parse_date = |date: Option<&str>, default: chrono::NaiveDate| match date {
+        Some(date) => {
+            chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| Status::BadRequest)
+        }
+        None => Ok(default),
+    };
+    let from = parse_date(from, chrono::Local::now().date_naive())?;
+    let to = parse_date(to, from)?;
+    if to < from {
+        return Err(Status::BadRequest);
+    }
+
+    let client = build_client();
+    info!("Logging in as {}...", &user.username);
+    let userinfo = login(&client, &user.username, &user.password)
+        .await
+        .map_err(|err| {
+            info!("Could not log in {}: {err}", user.username);
+            Status::Unauthorized
+        })?;
+    let substitutions = get_substitutions(&client, &userinfo.session_id, from, to).await;
+    info!("Logging out...");
+    logout(&client, &userinfo.session_id)
+        .await
+        .map_err(|_| Status::BadGateway)?;
+    let mut substitutions = substitutions.map_err(|err| {
+        error!("Could not retrieve substitutions: {err:#}");
+        Status::BadGateway
+    })?;
+
+    let matches = |name: &str, filter: &str| name.eq_ignore_ascii_case(filter.trim());
+    if let Some(class) = class {
+        substitutions.retain(|substitution| {
+            substitution
+                .klassen
+                .iter()
+                .any(|klasse| matches(&klasse.name, class))
+        });
+    }
+    if let Some(teacher) = teacher {
+        substitutions.retain(|substitution| {
+            substitution.teachers.iter().any(|substituted| {
+                matches(&substituted.name, teacher)
+                    || substituted
+                        .original_teacher
+                        .as_ref()
+                        .is_some_and(|original| matches(&original.name, teacher))
+            })
+        });
+    }
+    substitutions.sort_by_key(|substitution| {
+        chrono::NaiveDateTime::new(substitution.date, substitution.start_time)
+    });
+    Ok(Json(substitutions))
+}
@@ -0,0 +1,337 @@
+use anyhow::anyhow;
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use openssl::asn1::Asn1Time;
+use openssl::hash::MessageDigest;
+use openssl::sign::Verifier;
+use openssl::stack::Stack;
+use openssl::x509::store::X509StoreBuilder;
+use openssl::x509::{X509StoreContext, X509};
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Request};
+use rocket::serde::json::serde_json::{self, json};
+use rocket::serde::json::Json;
+use rocket::serde::Deserialize;
+use rocket::{request, State};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::accounts::{AccountStore, TokenOrigin};
+use crate::assistant::{assistant_answer, validate_signatures, AssistantAnswer, AssistantDay};
+use crate::language::Language;
+use crate::speech::templates::SpeechTemplates;
+use crate::speech::{Lexicon, Speech, SpeechFormat};
+use crate::untis::build_client;
+
+/// Signature headers of requests sent by Alexa
+pub(crate) struct AlexaSignature {
+    cert_chain_url: Option<String>,
+    signature: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AlexaSignature {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        request::Outcome::Success(AlexaSignature {
+            cert_chain_url: request
+                .headers()
+                .get_one("SignatureCertChainUrl")
+                .map(String::from),
+            signature: request.headers().get_one("Signature-256").map(String::from),
+        })
+    }
+}
+
+/// Checks, that the certificate chain is hosted where Amazon hosts its signing certificates
+fn verify_alexa_cert_chain_url(url: &str) -> anyhow::Result<()> {
+    let url = reqwest::Url::parse(url)?;
+    if !url.scheme().eq_ignore_ascii_case("https") {
+        return Err(anyhow!("certificate chain not served over https"));
+    }
+    if !url
+        .host_str()
+        .is_some_and(|host| host.eq_ignore_ascii_case("s3.amazonaws.com"))
+    {
+        return Err(anyhow!("certificate chain not served by s3.amazonaws.com"));
+    }
+    if url.port().is_some_and(|port| port != 443) {
+        return Err(anyhow!("certificate chain not served on port 443"));
+    }
+    let path = url.path().split('/').collect::<Vec<&str>>();
+    if path.get(1) != Some(&"echo.api") {
+        return Err(anyhow!("certificate chain not served from /echo.api/"));
+    }
+    Ok(())
+}
+
+/// Verifies the signature of a request sent by Alexa, as described in
+/// <https://developer.amazon.com/en-US/docs/alexa/custom-skills/host-a-custom-skill-as-a-web-service.html>
+async fn verify_alexa_signature(signature: &AlexaSignature, body: &str) -> anyhow::Result<()> {
+    static CERTIFICATES: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+
+    let url = signature
+        .cert_chain_url
+        .as_deref()
+        .ok_or(anyhow!("header 'SignatureCertChainUrl' missing"))?;
+    verify_alexa_cert_chain_url(url)?;
+    let signature = BASE64_STANDARD.decode(
+        signature
+            .signature
+            .as_deref()
+            .ok_or(anyhow!("header 'Signature-256' missing"))?,
+    )?;
+
+    let cached = CERTIFICATES
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .get(url)
+        .cloned();
+    let pem = match cached {
+        Some(pem) => pem,
+        None => {
+            debug!("Downloading Alexa certificate chain from {url}");
+            let pem = build_client().get(url).send().await?.text().await?;
+            CERTIFICATES
+                .lock()
+                .unwrap()
+                .get_or_insert_with(HashMap::new)
+                .insert(url.to_string(), pem.to_string());
+            pem
+        }
+    };
+
+    let chain = X509::stack_from_pem(pem.as_bytes())?;
+    let certificate = chain.first().ok_or(anyhow!("certificate chain is empty"))?;
+    let now = Asn1Time::days_from_now(0)?;
+    if certificate.not_before() > now || certificate.not_after() < now {
+        return Err(anyhow!("signing certificate is not valid at this time"));
+    }
+    if !certificate.subject_alt_names().is_some_and(|names| {
+        names
+            .iter()
+            .any(|name| name.dnsname() == Some("echo-api.amazon.com"))
+    }) {
+        return Err(anyhow!(
+            "signing certificate is not issued to echo-api.amazon.com"
+        ));
+    }
+
+    let mut store = X509StoreBuilder::new()?;
+    store.set_default_paths()?;
+    let store = store.build();
+    let mut intermediates = Stack::new()?;
+    for intermediate in chain.iter().skip(1) {
+        intermediates.push(intermediate.clone())?;
+    }
+    let mut context = X509StoreContext::new()?;
+    if !context.init(&store, certificate, &intermediates, |context| {
+        context.verify_cert()
+    })? {
+        return Err(anyhow!("signing certificate is not trusted"));
+    }
+
+    let key = certificate.public_key()?;
+    let mut verifier = Verifier::new(MessageDigest::sha256(), &key)?;
+    verifier.update(body.as_bytes())?;
+    if !verifier.verify(&signature)? {
+        return Err(anyhow!("signature does not match the request"));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct AlexaRequestEnvelope {
+    pub(crate) context: AlexaContext,
+    pub(crate) request: AlexaRequest,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct AlexaContext {
+    #[serde(rename = "System")]
+    system: AlexaSystem,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct AlexaSystem {
+    pub(crate) application: AlexaApplication,
+    pub(crate) user: AlexaUser,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct AlexaApplication {
+    #[serde(rename = "applicationId")]
+    application_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct AlexaUser {
+    #[serde(rename = "accessToken")]
+    pub(crate) access_token: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct AlexaRequest {
+    #[serde(rename = "type")]
+    request_type: String,
+    timestamp: chrono::DateTime<chrono::Utc>,
+    locale: Option<String>,
+    pub(crate) intent: Option<AlexaIntent>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct AlexaIntent {
+    pub(crate) name: String,
+    #[serde(default)]
+    slots: HashMap<String, AlexaSlot>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct AlexaSlot {
+    pub(crate) value: Option<String>,
+}
+
+/// Endpoint for an Alexa custom skill
+#[post("/alexa", data = "<body>")]
+pub(crate) async fn alexa(
+    body: Data<'_>,
+    signature: AlexaSignature,
+    accounts: &State<AccountStore>,
+    templates: &State<SpeechTemplates>,
+    lexicon: &State<Lexicon>,
+) -> Result<Json<serde_json::Value>, Status> {
+    // The signature is calculated over the exact bytes of the body
+    let body = body
+        .open(1.mebibytes())
+        .into_string()
+        .await
+        .map_err(|_| Status::BadRequest)?
+        .into_inner();
+    if validate_signatures() {
+        verify_alexa_signature(&signature, &body)
+            .await
+            .map_err(|err| {
+                warn!("Rejecting Alexa request: {err}");
+                Status::BadRequest
+            })?;
+    }
+    let envelope: AlexaRequestEnvelope = serde_json::from_str(&body).map_err(|err| {
+        warn!("Invalid Alexa request: {err}");
+        Status::BadRequest
+    })?;
+    if validate_signatures() {
+        let age = chrono::Utc::now() - envelope.request.timestamp;
+        if age.num_seconds().abs() > 150 {
+            warn!("Rejecting Alexa request with timestamp {age} off");
+            return Err(Status::BadRequest);
+        }
+    }
+    if let Ok(skill_id) = std::env::var("UNTIS_ALEXA_SKILL_ID") {
+        if envelope.context.system.application.application_id != skill_id {
+            warn!(
+                "Rejecting Alexa request for skill {}",
+                envelope.context.system.application.application_id
+            );
+            return Err(Status::BadRequest);
+        }
+    }
+
+    let language = envelope
+        .request
+        .locale
+        .as_deref()
+        .and_then(Language::from_tag)
+        .unwrap_or(Language::German);
+    let day = match (
+        envelope.request.request_type.as_str(),
+        &envelope.request.intent,
+    ) {
+        ("LaunchRequest", _) => Some(AssistantDay::Today),
+        ("IntentRequest", Some(intent))
+            if matches!(
+                intent.name.as_str(),
+                "AMAZON.StopIntent" | "AMAZON.CancelIntent"
+            ) =>
+        {
+            let speech = Speech {
+                language,
+                format: SpeechFormat::Text,
+                period_numbers: false,
+                lexicon,
+                account: "",
+            };
+            return Ok(Json(json!({
+                "version": "1.0",
+                "response": {
+                    "outputSpeech": {
+                        "type": "PlainText",
+                        "text": speech.translate("assistant-goodbye", &[]),
+                    },
+                    "shouldEndSession": true,
+                },
+            })));
+        }
+        ("IntentRequest", Some(intent)) => {
+            let slot = |name: &str| {
+                intent
+                    .slots
+                    .get(name)
+                    .and_then(|slot| slot.value.as_deref())
+            };
+            AssistantDay::from_intent(&intent.name, slot("weekday"), slot("date"))
+        }
+        // Session ends and other requests must not be answered with speech
+        _ => {
+            return Ok(Json(json!({
+                "version": "1.0",
+                "response": {},
+            })))
+        }
+    };
+
+    let user = envelope
+        .context
+        .system
+        .user
+        .access_token
+        .as_deref()
+        .and_then(|token| accounts.get(token, TokenOrigin::Assistant));
+    Ok(Json(
+        match assistant_answer(user, day, language, templates, lexicon).await {
+            AssistantAnswer::Tell { ssml, .. } => json!({
+                "version": "1.0",
+                "response": {
+                    "outputSpeech": {
+                        "type": "SSML",
+                        "ssml": ssml,
+                    },
+                    "shouldEndSession": true,
+                },
+            }),
+            AssistantAnswer::LinkAccount { text } => json!({
+                "version": "1.0",
+                "response": {
+                    "outputSpeech": {
+                        "type": "PlainText",
+                        "text": text,
+                    },
+                    "card": {
+                        "type": "LinkAccount",
+                    },
+                    "shouldEndSession": true,
+                },
+            }),
+        },
+    ))
+}
@@ -0,0 +1,140 @@
+use rocket::http::Status;
+use rocket::request::{FromRequest, Request};
+use rocket::serde::json::serde_json::{self, json};
+use rocket::serde::json::Json;
+use rocket::serde::Deserialize;
+use rocket::{request, State};
+use std::collections::HashMap;
+
+use crate::accounts::{AccountStore, TokenOrigin};
+use crate::admin::secret_matches;
+use crate::assistant::{assistant_answer, validate_signatures, AssistantAnswer, AssistantDay};
+use crate::language::Language;
+use crate::speech::templates::SpeechTemplates;
+use crate::speech::Lexicon;
+
+/// The `Authorization` header, Dialogflow is configured to send with fulfillment requests
+pub(crate) struct DialogflowAuthorization(pub(crate) Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for DialogflowAuthorization {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        request::Outcome::Success(DialogflowAuthorization(
+            request.headers().get_one("Authorization").map(String::from),
+        ))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct DialogflowRequest {
+    #[serde(rename = "queryResult")]
+    query_result: DialogflowQueryResult,
+    #[serde(rename = "originalDetectIntentRequest")]
+    original_request: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct DialogflowQueryResult {
+    pub(crate) intent: DialogflowIntent,
+    #[serde(default)]
+    pub(crate) parameters: HashMap<String, serde_json::Value>,
+    #[serde(rename = "languageCode")]
+    language_code: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct DialogflowIntent {
+    #[serde(rename = "displayName")]
+    pub(crate) display_name: String,
+}
+
+/// Fulfillment webhook for Dialogflow (ES) agents, e.g. for Google Assistant
+#[post("/dialogflow", data = "<request>")]
+pub(crate) async fn dialogflow(
+    request: Json<DialogflowRequest>,
+    authorization: DialogflowAuthorization,
+    accounts: &State<AccountStore>,
+    templates: &State<SpeechTemplates>,
+    lexicon: &State<Lexicon>,
+) -> Result<Json<serde_json::Value>, Status> {
+    if validate_signatures() {
+        let Ok(secret) = std::env::var("UNTIS_DIALOGFLOW_AUTHORIZATION") else {
+            warn!("Rejecting Dialogflow request: 'UNTIS_DIALOGFLOW_AUTHORIZATION' not defined!");
+            return Err(Status::Unauthorized);
+        };
+        if !authorization
+            .0
+            .as_deref()
+            .is_some_and(|given| secret_matches(given, &secret))
+        {
+            warn!("Rejecting Dialogflow request with wrong authorization");
+            return Err(Status::Unauthorized);
+        }
+    }
+
+    let query = &request.query_result;
+    let language = query
+        .language_code
+        .as_deref()
+        .and_then(Language::from_tag)
+        .unwrap_or(Language::German);
+    let parameter = |name: &str| {
+        query
+            .parameters
+            .get(name)
+            .and_then(|value| value.as_str())
+            .filter(|value| !value.is_empty())
+    };
+    let day = AssistantDay::from_intent(
+        &query.intent.display_name,
+        parameter("weekday"),
+        parameter("date"),
+    );
+    let user = request
+        .original_request
+        .as_ref()
+        .and_then(|original| original.pointer("/payload/user/accessToken"))
+        .and_then(|token| token.as_str())
+        .and_then(|token| accounts.get(token, TokenOrigin::Assistant));
+
+    Ok(Json(
+        match assistant_answer(user, day, language, templates, lexicon).await {
+            AssistantAnswer::Tell { text, ssml } => json!({
+                "fulfillmentText": text,
+                "payload": {
+                    "google": {
+                        "expectUserResponse": false,
+                        "richResponse": {
+                            "items": [{
+                                "simpleResponse": {
+                                    "ssml": ssml,
+                                    "displayText": text,
+                                },
+                            }],
+                        },
+                    },
+                },
+            }),
+            AssistantAnswer::LinkAccount { text } => json!({
+                "fulfillmentText": text,
+                "payload": {
+                    "google": {
+                        "expectUserResponse": true,
+                        "systemIntent": {
+                            "intent": "actions.intent.SIGN_IN",
+                            "data": {
+                                "@type": "type.googleapis.com/google.actions.v2.SignInValueSpec",
+                                "optContext": text,
+                            },
+                        },
+                    },
+                },
+            }),
+        },
+    ))
+}
@@ -0,0 +1,286 @@
+pub(crate) mod alexa;
+pub(crate) mod dialogflow;
+
+use chrono::Datelike;
+use rocket::form::Form;
+use rocket::http::{RawStr, Status};
+use rocket::response::content::RawHtml;
+use rocket::response::Redirect;
+use rocket::serde::Serialize;
+use rocket::State;
+use std::collections::HashMap;
+
+use crate::accounts::{AccountStore, TokenOrigin};
+use crate::dashboard::{html_texts, render_html};
+use crate::language::Language;
+use crate::speech::templates::SpeechTemplates;
+use crate::speech::{
+    speakable_day, speakable_day_name, speakable_holiday, Lexicon, Speech, SpeechFormat,
+};
+use crate::untis::calendar::{school_calendar, SchoolCalendar};
+use crate::untis::timetable::{fetch_timetable, merged_timetable, parse_mode};
+use crate::untis::{build_client, login, logout, UsernamePassword};
+
+/// Whether incoming voice assistant requests are checked for authenticity. Can be disabled with
+/// `UNTIS_SKIP_SIGNATURE_VALIDATION=true` for local testing.
+pub(crate) fn validate_signatures() -> bool {
+    !std::env::var("UNTIS_SKIP_SIGNATURE_VALIDATION")
+        .map(|skip| skip.eq_ignore_ascii_case("true") || skip == "1")
+        .unwrap_or(false)
+}
+
+#[derive(FromForm)]
+pub(crate) struct LinkForm {
+    pub(crate) username: String,
+    pub(crate) password: String,
+    redirect_uri: String,
+    pub(crate) state: String,
+}
+
+/// Only redirect URIs listed in `UNTIS_LINK_REDIRECT_URIS` receive access tokens
+fn allowed_redirect_uri(redirect_uri: &str) -> bool {
+    std::env::var("UNTIS_LINK_REDIRECT_URIS")
+        .unwrap_or_default()
+        .split(',')
+        .any(|allowed| !allowed.trim().is_empty() && allowed.trim() == redirect_uri)
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct LinkPage<'a> {
+    pub(crate) lang: &'static str,
+    pub(crate) t: HashMap<&'static str, String>,
+    redirect_uri: &'a str,
+    pub(crate) state: &'a str,
+}
+
+/// Login page for the implicit grant account linking of Alexa and Google
+#[get("/link?<redirect_uri>&<state>")]
+pub(crate) fn link_form(
+    redirect_uri: &str,
+    state: &str,
+    language: Language,
+) -> Result<RawHtml<String>, Status> {
+    if !allowed_redirect_uri(redirect_uri) {
+        warn!("Refusing account linking for redirect URI '{redirect_uri}'");
+        return Err(Status::BadRequest);
+    }
+    render_html(
+        "link.html",
+        LinkPage {
+            lang: language.tag(),
+            t: html_texts(language),
+            redirect_uri,
+            state,
+        },
+    )
+}
+
+#[post("/link", data = "<form>")]
+pub(crate) async fn link(
+    form: Form<LinkForm>,
+    accounts: &State<AccountStore>,
+) -> Result<Redirect, Status> {
+    if !allowed_redirect_uri(&form.redirect_uri) {
+        warn!(
+            "Refusing account linking for redirect URI '{}'",
+            form.redirect_uri
+        );
+        return Err(Status::BadRequest);
+    }
+
+    // Make sure the credentials work, before handing out a token for them
+    let client = build_client();
+    info!("Verifying credentials of {} for linking...", form.username);
+    let userinfo = login(&client, &form.username, &form.password)
+        .await
+        .map_err(|err| {
+            info!("Could not link account of {}: {err}", form.username);
+            Status::Unauthorized
+        })?;
+    logout(&client, &userinfo.session_id)
+        .await
+        .map_err(|_| Status::BadGateway)?;
+
+    let token = accounts
+        .link(
+            UsernamePassword {
+                username: form.username.to_string(),
+                password: form.password.to_string(),
+            },
+            TokenOrigin::Assistant,
+        )
+        .map_err(|err| {
+            error!("Could not store linked account: {err}");
+            Status::InternalServerError
+        })?;
+    Ok(Redirect::to(format!(
+        "{}#state={}&access_token={}&token_type=Bearer",
+        form.redirect_uri,
+        RawStr::new(&form.state).percent_encode(),
+        token
+    )))
+}
+
+/// The day a voice assistant user is asking about
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum AssistantDay {
+    Today,
+    Tomorrow,
+    /// The next day with school, skipping weekends and holidays
+    NextSchoolDay,
+    Weekday(chrono::Weekday),
+    Date(chrono::NaiveDate),
+}
+
+impl AssistantDay {
+    /// Maps an intent (`TodayIntent`, `TomorrowIntent`, `NextSchoolDayIntent`, `WeekdayIntent` on
+    /// Alexa, `today`, `tomorrow`, `nextschoolday` and `weekday` on Dialogflow) and its parameters
+    /// to a day
+    pub(crate) fn from_intent(
+        intent: &str,
+        weekday: Option<&str>,
+        date: Option<&str>,
+    ) -> Option<Self> {
+        let intent = intent.to_lowercase();
+        match intent.trim_end_matches("intent") {
+            "today" => Some(AssistantDay::Today),
+            "tomorrow" => Some(AssistantDay::Tomorrow),
+            "nextschoolday" | "next_school_day" => Some(AssistantDay::NextSchoolDay),
+            "weekday" | "day" => {
+                if let Some(date) = date
+                    .and_then(|date| date.get(0..10))
+                    .and_then(|date| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+                {
+                    return Some(AssistantDay::Date(date));
+                }
+                weekday.and_then(parse_weekday).map(AssistantDay::Weekday)
+            }
+            _ => None,
+        }
+    }
+
+    /// The date of this day, as seen from `today`. Weekdays refer to the next occurrence,
+    /// including today.
+    pub(crate) fn date(
+        &self,
+        today: chrono::NaiveDate,
+        calendar: &SchoolCalendar,
+    ) -> chrono::NaiveDate {
+        match self {
+            AssistantDay::Today => today,
+            AssistantDay::Tomorrow => today + chrono::Duration::days(1),
+            AssistantDay::NextSchoolDay => calendar
+                .next_school_day(today)
+                .unwrap_or(today + chrono::Duration::days(1)),
+            AssistantDay::Weekday(weekday) => {
+                let days = (weekday.num_days_from_monday() + 7
+                    - today.weekday().num_days_from_monday())
+                    % 7;
+                today + chrono::Duration::days(days as i64)
+            }
+            AssistantDay::Date(date) => *date,
+        }
+    }
+}
+
+pub(crate) fn weekday_message(weekday: chrono::Weekday) -> &'static str {
+    match weekday {
+        chrono::Weekday::Mon => "weekday-mon",
+        chrono::Weekday::Tue => "weekday-tue",
+        chrono::Weekday::Wed => "weekday-wed",
+        chrono::Weekday::Thu => "weekday-thu",
+        chrono::Weekday::Fri => "weekday-fri",
+        chrono::Weekday::Sat => "weekday-sat",
+        chrono::Weekday::Sun => "weekday-sun",
+    }
+}
+
+/// Parses English (`monday`, `mon`) and German (`Montag`) weekday names
+fn parse_weekday(name: &str) -> Option<chrono::Weekday> {
+    let name = name.trim().to_lowercase();
+    match name.as_str() {
+        "montag" => Some(chrono::Weekday::Mon),
+        "dienstag" => Some(chrono::Weekday::Tue),
+        "mittwoch" => Some(chrono::Weekday::Wed),
+        "donnerstag" => Some(chrono::Weekday::Thu),
+        "freitag" => Some(chrono::Weekday::Fri),
+        "samstag" | "sonnabend" => Some(chrono::Weekday::Sat),
+        "sonntag" => Some(chrono::Weekday::Sun),
+        _ => name.parse().ok(),
+    }
+}
+
+/// What a voice assistant should answer
+pub(crate) enum AssistantAnswer {
+    /// The answer to a question, after which the session ends
+    Tell { text: String, ssml: String },
+    /// The user has to link their WebUntis account first
+    LinkAccount { text: String },
+}
+
+/// Answers the question of a voice assistant user about the changes on `day`
+pub(crate) async fn assistant_answer(
+    user: Option<UsernamePassword>,
+    day: Option<AssistantDay>,
+    language: Language,
+    templates: &SpeechTemplates,
+    lexicon: &Lexicon,
+) -> AssistantAnswer {
+    let text = Speech {
+        language,
+        format: SpeechFormat::Text,
+        period_numbers: false,
+        lexicon,
+        account: user.as_ref().map_or("", |user| user.username.as_str()),
+    };
+    let ssml = Speech {
+        format: SpeechFormat::Ssml,
+        ..text
+    };
+    let Some(user) = user.as_ref() else {
+        return AssistantAnswer::LinkAccount {
+            text: text.translate("assistant-link-account", &[]),
+        };
+    };
+    let Some(day) = day else {
+        return AssistantAnswer::Tell {
+            text: text.translate("assistant-unknown-intent", &[]),
+            ssml: ssml.join(vec![ssml.translate("assistant-unknown-intent", &[])]),
+        };
+    };
+
+    let today = chrono::Local::now().date_naive();
+    let calendar = school_calendar(user).await;
+    let date = day.date(today, &calendar);
+    if let Some(holiday) = calendar.holiday(date) {
+        return AssistantAnswer::Tell {
+            text: speakable_holiday(text, holiday, date, today),
+            ssml: ssml.join(vec![speakable_holiday(ssml, holiday, date, today)]),
+        };
+    }
+    let timetable = match fetch_timetable(user, None, date, parse_mode()).await {
+        Ok(Some((timetable, _))) => merged_timetable(timetable),
+        Ok(None) | Err(_) => {
+            return AssistantAnswer::Tell {
+                text: text.translate("assistant-error", &[]),
+                ssml: ssml.join(vec![ssml.translate("assistant-error", &[])]),
+            }
+        }
+    };
+
+    let answer = |speech: Speech| {
+        let mut sentences = speakable_day(&timetable, date, speech, templates);
+        if sentences.is_empty() {
+            sentences.push(speech.translate(
+                "assistant-no-changes",
+                &[("day", &speakable_day_name(speech, date, today))],
+            ));
+        }
+        speech.join(sentences)
+    };
+    AssistantAnswer::Tell {
+        text: answer(text),
+        ssml: answer(ssml),
+    }
+}
@@ -0,0 +1,526 @@
+use chrono::Datelike;
+use rocket::form::Form;
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::response::content::RawHtml;
+use rocket::response::Redirect;
+use rocket::serde::Serialize;
+use rocket::State;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::accounts::{AccountStore, TokenOrigin, DASHBOARD_SESSION_DAYS};
+use crate::assistant::weekday_message;
+use crate::language::Language;
+use crate::untis::messages::{fetch_day_messages, DayMessage};
+use crate::untis::timetable::{
+    fetch_timetable, merged_timetable, parse_mode, ElementState, Period, PeriodState,
+};
+use crate::untis::{build_client, login, logout, UsernamePassword};
+
+/// The HTML pages of the dashboard, bundled into the binary
+fn html_templates() -> &'static minijinja::Environment<'static> {
+    static TEMPLATES: OnceLock<minijinja::Environment<'static>> = OnceLock::new();
+    TEMPLATES.get_or_init(|| {
+        let mut environment = minijinja::Environment::new();
+        environment.set_undefined_behavior(minijinja::UndefinedBehavior::Strict);
+        environment
+            .add_template("login.html", include_str!("templates/login.html"))
+            .expect("Could not parse bundled login page!");
+        environment
+            .add_template("dashboard.html", include_str!("templates/dashboard.html"))
+            .expect("Could not parse bundled dashboard!");
+        environment
+            .add_template("kiosk.html", include_str!("templates/kiosk.html"))
+            .expect("Could not parse bundled kiosk page!");
+        environment
+            .add_template("link.html", include_str!("templates/link.html"))
+            .expect("Could not parse bundled account linking page!");
+        environment
+    })
+}
+
+pub(crate) fn render_html(name: &str, context: impl Serialize) -> Result<RawHtml<String>, Status> {
+    html_templates()
+        .get_template(name)
+        .and_then(|template| template.render(context))
+        .map(RawHtml)
+        .map_err(|err| {
+            error!("Could not render {name}: {err:#}");
+            Status::InternalServerError
+        })
+}
+
+/// The fixed texts of the dashboard and account linking pages, by the name used in the templates
+pub(crate) fn html_texts(language: Language) -> HashMap<&'static str, String> {
+    [
+        ("title", "dashboard-title"),
+        ("previous", "dashboard-previous"),
+        ("current", "dashboard-current"),
+        ("next", "dashboard-next"),
+        ("logout", "dashboard-logout"),
+        ("no_lessons", "dashboard-no-lessons"),
+        ("login", "dashboard-login"),
+        ("username", "dashboard-username"),
+        ("password", "dashboard-password"),
+        ("login_failed", "dashboard-login-failed"),
+        ("link_title", "link-title"),
+        ("link", "link-submit"),
+    ]
+    .into_iter()
+    .map(|(name, id)| (name, language.translate(id, &[])))
+    .collect()
+}
+
+/// A WebUntis colour (`f0e68c` or `#f0e68c`) as CSS colour, if it is one
+fn css_color(color: &str) -> Option<String> {
+    let hex = color.trim().trim_start_matches('#');
+    (matches!(hex.len(), 3 | 6 | 8) && hex.chars().all(|char| char.is_ascii_hexdigit()))
+        .then(|| format!("#{hex}"))
+}
+
+/// Assigns overlapping periods (e.g. of parallel groups) to lanes next to each other. Returns
+/// the lane of each period and the number of lanes of the periods it overlaps with. The periods
+/// have to be sorted by their start time.
+fn period_lanes(periods: &[&Period]) -> Vec<(usize, usize)> {
+    let mut lanes = vec![(0, 1); periods.len()];
+    let mut group_start = 0;
+    let mut group_end: Option<chrono::NaiveTime> = None;
+    let mut lane_ends: Vec<chrono::NaiveTime> = vec![];
+    for (index, period) in periods.iter().enumerate() {
+        if group_end.is_some_and(|end| period.start_time >= end) {
+            for lane in &mut lanes[group_start..index] {
+                lane.1 = lane_ends.len();
+            }
+            group_start = index;
+            lane_ends.clear();
+        }
+        let lane = match lane_ends.iter().position(|end| *end <= period.start_time) {
+            Some(lane) => {
+                lane_ends[lane] = period.end_time;
+                lane
+            }
+            None => {
+                lane_ends.push(period.end_time);
+                lane_ends.len() - 1
+            }
+        };
+        lanes[index].0 = lane;
+        group_end = Some(group_end.map_or(period.end_time, |end| end.max(period.end_time)));
+    }
+    for lane in &mut lanes[group_start..] {
+        lane.1 = lane_ends.len();
+    }
+    lanes
+}
+
+/// A teacher or room as shown on the dashboard and the kiosk
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct DisplayElement {
+    pub(crate) name: String,
+    /// Set, if the element replaces another one
+    pub(crate) original: Option<String>,
+}
+
+impl DisplayElement {
+    pub(crate) fn teachers(period: &Period) -> Vec<Self> {
+        period
+            .teachers
+            .iter()
+            .map(|teacher| DisplayElement {
+                name: teacher.name.to_string(),
+                original: teacher
+                    .original_teacher
+                    .as_ref()
+                    .map(|original| original.name.to_string()),
+            })
+            .collect()
+    }
+
+    pub(crate) fn rooms(period: &Period) -> Vec<Self> {
+        period
+            .rooms
+            .iter()
+            .map(|room| DisplayElement {
+                name: room.name.to_string(),
+                original: room
+                    .original_room
+                    .as_ref()
+                    .map(|original| original.name.to_string()),
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct DashboardPeriod {
+    pub(crate) subject: String,
+    pub(crate) teachers: Vec<DisplayElement>,
+    pub(crate) rooms: Vec<DisplayElement>,
+    pub(crate) start: String,
+    pub(crate) end: String,
+    /// CSS class of the period state
+    pub(crate) state: &'static str,
+    state_label: Option<String>,
+    /// Shown when hovering over the period
+    details: String,
+    /// Position in pixels from the start of the earliest period of the week
+    top: i64,
+    height: i64,
+    /// Horizontal position in percent, for periods taking place at the same time
+    pub(crate) left: f64,
+    width: f64,
+    pub(crate) back_color: String,
+    pub(crate) fore_color: String,
+}
+
+impl DashboardPeriod {
+    /// Vertical scale of the timetable grid
+    const PIXELS_PER_MINUTE: f64 = 1.5;
+
+    pub(crate) fn new(
+        period: &Period,
+        language: Language,
+        day_start: chrono::NaiveTime,
+        (lane, lanes): (usize, usize),
+    ) -> Self {
+        let subject = period.subjects.first();
+        let teachers = DisplayElement::teachers(period);
+        let rooms = DisplayElement::rooms(period);
+
+        let mut details = vec![period
+            .subjects
+            .iter()
+            .map(|subject| subject.long_name.as_str())
+            .collect::<Vec<&str>>()
+            .join(", ")];
+        for (prefix, elements, states) in [
+            (
+                "dashboard-teacher",
+                &teachers,
+                period
+                    .teachers
+                    .iter()
+                    .map(|teacher| &teacher.state)
+                    .collect::<Vec<&ElementState>>(),
+            ),
+            (
+                "dashboard-room",
+                &rooms,
+                period
+                    .rooms
+                    .iter()
+                    .map(|room| &room.state)
+                    .collect::<Vec<&ElementState>>(),
+            ),
+        ] {
+            for (element, state) in elements.iter().zip(states) {
+                let name = element.name.as_str();
+                details.push(match (state, &element.original) {
+                    (ElementState::Absent, Some(original)) => {
+                        language.translate(&format!("{prefix}-absent"), &[("original", original)])
+                    }
+                    (_, Some(original)) => language.translate(
+                        &format!("{prefix}-changed"),
+                        &[("name", name), ("original", original)],
+                    ),
+                    (_, None) => language.translate(prefix, &[("name", name)]),
+                });
+            }
+        }
+        for text in [&period.substitution_text, &period.lesson_text, &period.info] {
+            if !text.is_empty() {
+                details.push(text.to_string());
+            }
+        }
+
+        let minutes = |time: chrono::NaiveTime| (time - day_start).num_minutes() as f64;
+        DashboardPeriod {
+            subject: subject
+                .map(|subject| subject.name.to_string())
+                .unwrap_or_else(|| period.substitution_text.to_string()),
+            teachers,
+            rooms,
+            start: language.format_time(period.start_time),
+            end: language.format_time(period.end_time),
+            state: period.state.template_name(),
+            state_label: (period.state != PeriodState::Standard).then(|| {
+                language.translate(
+                    &format!("state-{}", period.state.template_name().replace('_', "-")),
+                    &[],
+                )
+            }),
+            details: details
+                .into_iter()
+                .filter(|detail| !detail.is_empty())
+                .collect::<Vec<String>>()
+                .join("\n"),
+            top: (minutes(period.start_time) * Self::PIXELS_PER_MINUTE).round() as i64,
+            height: ((minutes(period.end_time) - minutes(period.start_time))
+                * Self::PIXELS_PER_MINUTE)
+                .round() as i64,
+            left: 100.0 * lane as f64 / lanes as f64,
+            width: 100.0 / lanes as f64,
+            back_color: subject
+                .and_then(|subject| css_color(&subject.back_color))
+                .unwrap_or_else(|| String::from("#e0e0e0")),
+            fore_color: subject
+                .and_then(|subject| subject.fore_color.as_deref())
+                .and_then(css_color)
+                .unwrap_or_else(|| String::from("#000000")),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct DashboardDay {
+    pub(crate) name: String,
+    pub(crate) date: String,
+    pub(crate) messages: Vec<DayMessage>,
+    pub(crate) periods: Vec<DashboardPeriod>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct DashboardPage {
+    pub(crate) lang: &'static str,
+    pub(crate) t: HashMap<&'static str, String>,
+    pub(crate) week: String,
+    pub(crate) previous: String,
+    pub(crate) next: String,
+    /// Whether the messages of the day are shown
+    pub(crate) news: bool,
+    pub(crate) error: Option<String>,
+    /// Height of the day columns in pixels
+    height: i64,
+    pub(crate) days: Vec<DashboardDay>,
+}
+
+impl DashboardPage {
+    /// The Monday of the week of `date`. `None`, if that week or the weeks before and after it are
+    /// beyond the dates that can be represented.
+    pub(crate) fn monday(date: chrono::NaiveDate) -> Option<chrono::NaiveDate> {
+        let monday = date.checked_sub_days(chrono::Days::new(
+            date.weekday().num_days_from_monday() as u64,
+        ))?;
+        monday.checked_sub_days(chrono::Days::new(7))?;
+        monday.checked_add_days(chrono::Days::new(13))?;
+        Some(monday)
+    }
+
+    /// The days of the week starting on `monday` (as returned by [`DashboardPage::monday`]).
+    /// Weekends are only included, if there are periods on them.
+    pub(crate) fn days(timetable: &[Period], monday: chrono::NaiveDate) -> Vec<chrono::NaiveDate> {
+        (0..7)
+            .map(|offset| monday + chrono::Days::new(offset))
+            .filter(|day| {
+                day.weekday().num_days_from_monday() < 5
+                    || timetable.iter().any(|period| period.date == *day)
+            })
+            .collect()
+    }
+
+    /// The week starting on `monday` (as returned by [`DashboardPage::monday`]) as a grid with one
+    /// column per day, optionally with the messages of each day above its periods
+    pub(crate) fn new(
+        timetable: &[Period],
+        monday: chrono::NaiveDate,
+        language: Language,
+        messages: Option<&HashMap<chrono::NaiveDate, Vec<DayMessage>>>,
+        error: Option<String>,
+    ) -> Self {
+        let days = Self::days(timetable, monday);
+        let day_start = timetable
+            .iter()
+            .map(|period| period.start_time)
+            .min()
+            .unwrap_or_default();
+        let day_end = timetable
+            .iter()
+            .map(|period| period.end_time)
+            .max()
+            .unwrap_or_default();
+
+        DashboardPage {
+            lang: language.tag(),
+            t: html_texts(language),
+            week: language.translate(
+                "dashboard-week",
+                &[
+                    ("week", &monday.iso_week().week().to_string()),
+                    ("start", &language.format_date(monday)),
+                    ("end", &language.format_date(*days.last().unwrap())),
+                ],
+            ),
+            previous: (monday - chrono::Days::new(7)).to_string(),
+            next: (monday + chrono::Days::new(7)).to_string(),
+            news: messages.is_some(),
+            error,
+            height: ((day_end - day_start).num_minutes() as f64
+                * DashboardPeriod::PIXELS_PER_MINUTE)
+                .round() as i64,
+            days: days
+                .into_iter()
+                .map(|day| {
+                    let periods = timetable
+                        .iter()
+                        .filter(|period| period.date == day)
+                        .collect::<Vec<&Period>>();
+                    DashboardDay {
+                        name: language.translate(weekday_message(day.weekday()), &[]),
+                        date: language.format_date(day),
+                        messages: messages
+                            .and_then(|messages| messages.get(&day))
+                            .cloned()
+                            .unwrap_or_default(),
+                        periods: periods
+                            .iter()
+                            .zip(period_lanes(&periods))
+                            .map(|(period, lanes)| {
+                                DashboardPeriod::new(period, language, day_start, lanes)
+                            })
+                            .collect(),
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct LoginPage {
+    pub(crate) lang: &'static str,
+    pub(crate) t: HashMap<&'static str, String>,
+    pub(crate) failed: bool,
+}
+
+/// Cookie holding the access token of the account logged in to the dashboard
+const ACCOUNT_COOKIE: &str = "untis_account";
+
+/// Dashboard showing the week of `week` (any date, by default today) of the logged in user,
+/// optionally with the messages of the day. `400 Bad Request`, if the week or its neighbours are
+/// beyond the dates that can be represented.
+#[get("/?<week>&<news>&<login_failed>")]
+pub(crate) async fn index(
+    week: Option<&str>,
+    news: Option<bool>,
+    login_failed: Option<bool>,
+    cookies: &CookieJar<'_>,
+    accounts: &State<AccountStore>,
+    language: Language,
+) -> Result<RawHtml<String>, Status> {
+    let Some(user) = cookies
+        .get(ACCOUNT_COOKIE)
+        .and_then(|cookie| accounts.get(cookie.value(), TokenOrigin::Dashboard))
+    else {
+        return render_html(
+            "login.html",
+            LoginPage {
+                lang: language.tag(),
+                t: html_texts(language),
+                failed: login_failed.unwrap_or(false),
+            },
+        );
+    };
+
+    let date = week
+        .and_then(|week| chrono::NaiveDate::parse_from_str(week, "%Y-%m-%d").ok())
+        .unwrap_or_else(|| chrono::Local::now().date_naive());
+    let monday = DashboardPage::monday(date).ok_or(Status::BadRequest)?;
+    let (timetable, error) = match fetch_timetable(&user, None, date, parse_mode()).await {
+        Ok(Some((timetable, _))) => (merged_timetable(timetable), None),
+        Ok(None) | Err(_) => (vec![], Some(language.translate("dashboard-error", &[]))),
+    };
+    let messages = if news.unwrap_or(false) {
+        let days = DashboardPage::days(&timetable, monday);
+        Some(
+            fetch_day_messages(&user, &days)
+                .await
+                .inspect_err(|err| warn!("Could not retrieve messages of the day: {err:#}"))
+                .unwrap_or_default(),
+        )
+    } else {
+        None
+    };
+    render_html(
+        "dashboard.html",
+        DashboardPage::new(&timetable, monday, language, messages.as_ref(), error),
+    )
+}
+
+#[derive(FromForm)]
+pub(crate) struct LoginForm {
+    pub(crate) username: String,
+    pub(crate) password: String,
+}
+
+/// Logs in to the dashboard. The credentials are kept like those of linked voice assistant
+/// accounts, the browser only gets an access token, that is valid for the dashboard only.
+#[post("/login", data = "<form>")]
+pub(crate) async fn dashboard_login(
+    form: Form<LoginForm>,
+    cookies: &CookieJar<'_>,
+    accounts: &State<AccountStore>,
+) -> Result<Redirect, Status> {
+    let client = build_client();
+    info!(
+        "Verifying credentials of {} for the dashboard...",
+        form.username
+    );
+    let userinfo = match login(&client, &form.username, &form.password).await {
+        Ok(userinfo) => userinfo,
+        Err(err) => {
+            info!("Could not log in {} to the dashboard: {err}", form.username);
+            return Ok(Redirect::to("/?login_failed=true"));
+        }
+    };
+    logout(&client, &userinfo.session_id)
+        .await
+        .map_err(|_| Status::BadGateway)?;
+
+    let token = accounts
+        .link(
+            UsernamePassword {
+                username: form.username.to_string(),
+                password: form.password.to_string(),
+            },
+            TokenOrigin::Dashboard,
+        )
+        .map_err(|err| {
+            error!("Could not store dashboard account: {err}");
+            Status::InternalServerError
+        })?;
+    cookies.add(
+        Cookie::build((ACCOUNT_COOKIE, token))
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .secure(secure_cookie())
+            .max_age(rocket::time::Duration::days(DASHBOARD_SESSION_DAYS)),
+    );
+    Ok(Redirect::to("/"))
+}
+
+/// Whether the dashboard cookie is only sent over HTTPS. Can be disabled with
+/// `UNTIS_SECURE_COOKIE=false` for dashboards served over plain HTTP in a local network.
+fn secure_cookie() -> bool {
+    !std::env::var("UNTIS_SECURE_COOKIE")
+        .map(|secure| secure.eq_ignore_ascii_case("false") || secure == "0")
+        .unwrap_or(false)
+}
+
+#[post("/logout")]
+pub(crate) fn dashboard_logout(
+    cookies: &CookieJar<'_>,
+    accounts: &State<AccountStore>,
+) -> Redirect {
+    if let Some(cookie) = cookies.get(ACCOUNT_COOKIE) {
+        if let Err(err) = accounts.unlink(cookie.value(), TokenOrigin::Dashboard) {
+            error!("Could not remove dashboard account: {err}");
+        }
+    }
+    cookies.remove(ACCOUNT_COOKIE);
+    Redirect::to("/")
+}
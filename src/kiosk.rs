@@ -0,0 +1,426 @@
+use anyhow::anyhow;
+use chrono::Datelike;
+use rocket::http::Status;
+use rocket::response::content::RawHtml;
+use rocket::serde::json::Json;
+use rocket::serde::Serialize;
+use rocket::State;
+use std::collections::HashMap;
+
+use crate::assistant::weekday_message;
+use crate::dashboard::{html_texts, render_html, DisplayElement};
+use crate::language::Language;
+use crate::speech::templates::SpeechTemplates;
+use crate::untis::availability::parse_element_timetable;
+use crate::untis::calendar::school_calendar;
+use crate::untis::timegrid::{get_timegrid, PeriodNumbers};
+use crate::untis::timetable::{
+    get_elements, get_timetable, merged_timetable, prepare_timetable, ElementType, Period,
+    PeriodState, TimetableElement,
+};
+use crate::untis::{build_client, login, logout, UsernamePassword};
+
+/// Credentials of the account the kiosk retrieves the class timetables with. The kiosk is
+/// disabled without `UNTIS_KIOSK_USERNAME` and `UNTIS_KIOSK_PASSWORD`.
+fn kiosk_account() -> Option<UsernamePassword> {
+    Some(UsernamePassword {
+        username: std::env::var("UNTIS_KIOSK_USERNAME").ok()?,
+        password: std::env::var("UNTIS_KIOSK_PASSWORD").ok()?,
+    })
+}
+
+/// Retrieves the timetables of all classes visible to the user on `dates`, using one session
+async fn fetch_klassen_timetables(
+    user: &UsernamePassword,
+    dates: &[chrono::NaiveDate],
+) -> anyhow::Result<Vec<(TimetableElement, Vec<Period>)>> {
+    let client = build_client();
+
+    info!("Logging in as {}...", &user.username);
+    let userinfo = login(&client, &user.username, &user.password).await?;
+    let session_id = userinfo.session_id.as_str();
+    let timetables = async {
+        let klassen = get_elements(&client, session_id, ElementType::Klasse).await?;
+        let timegrid = get_timegrid(&client, session_id)
+            .await
+            .inspect_err(|err| warn!("Could not retrieve time grid: {err}"))
+            .ok();
+        let mut weeks = dates
+            .iter()
+            .map(|date| *date - chrono::Days::new(date.weekday().num_days_from_monday() as u64))
+            .collect::<Vec<chrono::NaiveDate>>();
+        weeks.dedup();
+
+        info!("Retrieving timetables of {} classes...", klassen.len());
+        let klassen = klassen
+            .into_iter()
+            .filter(|klasse| klasse.can_view_timetable)
+            .collect::<Vec<TimetableElement>>();
+        let mut timetables = vec![];
+        let mut failed = 0;
+        for klasse in klassen.iter() {
+            let mut periods = vec![];
+            let fetched = async {
+                for week in &weeks {
+                    let timetable =
+                        get_timetable(&client, session_id, ElementType::Klasse, klasse.id, *week)
+                            .await?;
+                    periods.extend(parse_element_timetable(timetable, klasse.id)?);
+                }
+                anyhow::Ok(())
+            }
+            .await;
+            // One broken class timetable should not take down the whole kiosk
+            if let Err(err) = fetched {
+                warn!("Skipping class {}: {err:#}", klasse.name);
+                failed += 1;
+                continue;
+            }
+            periods.retain(|period| dates.contains(&period.date));
+            timetables.push((
+                klasse.clone(),
+                merged_timetable(prepare_timetable(periods, timegrid)),
+            ));
+        }
+        if failed > 0 && failed == klassen.len() {
+            return Err(anyhow!("Could not retrieve the timetable of any class"));
+        }
+        anyhow::Ok(timetables)
+    }
+    .await;
+    info!("Logging out...");
+    logout(&client, session_id).await?;
+    timetables
+}
+
+/// A changed period on the kiosk
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct KioskEntry {
+    /// Period numbers (`3.` or `3.–4.`), or start and end time without a time grid
+    pub(crate) period: String,
+    #[serde(rename = "startTime")]
+    pub(crate) start_time: chrono::NaiveTime,
+    #[serde(rename = "endTime")]
+    pub(crate) end_time: chrono::NaiveTime,
+    /// Name of the [`PeriodState`], as used for the speech templates
+    pub(crate) state: &'static str,
+    pub(crate) subject: String,
+    pub(crate) teachers: Vec<DisplayElement>,
+    pub(crate) rooms: Vec<DisplayElement>,
+    pub(crate) text: String,
+}
+
+impl KioskEntry {
+    pub(crate) fn new(period: &Period) -> Self {
+        KioskEntry {
+            period: match period.period_numbers {
+                Some(PeriodNumbers { first, last }) if first == last => format!("{first}."),
+                Some(PeriodNumbers { first, last }) => format!("{first}.–{last}."),
+                None => format!(
+                    "{}–{}",
+                    period.start_time.format("%H:%M"),
+                    period.end_time.format("%H:%M")
+                ),
+            },
+            start_time: period.start_time,
+            end_time: period.end_time,
+            state: period.state.template_name(),
+            subject: period
+                .subjects
+                .iter()
+                .map(|subject| subject.name.as_str())
+                .collect::<Vec<&str>>()
+                .join(", "),
+            teachers: DisplayElement::teachers(period),
+            rooms: DisplayElement::rooms(period),
+            text: [&period.substitution_text, &period.info]
+                .into_iter()
+                .filter(|text| !text.is_empty())
+                .map(String::as_str)
+                .collect::<Vec<&str>>()
+                .join(" – "),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct KioskClass {
+    pub(crate) name: String,
+    pub(crate) entries: Vec<KioskEntry>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct KioskDay {
+    pub(crate) date: chrono::NaiveDate,
+    pub(crate) classes: Vec<KioskClass>,
+}
+
+/// The changes of all classes today and on the next school day, grouped by class and sorted by
+/// period
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct KioskFeed {
+    updated: chrono::NaiveDateTime,
+    /// Starting at 1
+    pub(crate) page: usize,
+    pub(crate) pages: usize,
+    pub(crate) days: Vec<KioskDay>,
+}
+
+impl KioskFeed {
+    pub(crate) fn new(
+        timetables: Vec<(TimetableElement, Vec<Period>)>,
+        dates: &[chrono::NaiveDate],
+    ) -> Self {
+        let mut timetables = timetables;
+        // "5a" before "10a"
+        timetables.sort_by_cached_key(|(klasse, _)| {
+            let digits = klasse
+                .name
+                .chars()
+                .take_while(|char| char.is_ascii_digit())
+                .collect::<String>();
+            (
+                digits.parse::<u32>().unwrap_or(0),
+                klasse.name.to_lowercase(),
+            )
+        });
+        KioskFeed {
+            updated: chrono::Local::now().naive_local(),
+            page: 1,
+            pages: 1,
+            days: dates
+                .iter()
+                .map(|date| KioskDay {
+                    date: *date,
+                    classes: timetables
+                        .iter()
+                        .map(|(klasse, periods)| KioskClass {
+                            name: klasse.name.to_string(),
+                            entries: periods
+                                .iter()
+                                .filter(|period| period.date == *date)
+                                .filter(|period| period.state != PeriodState::Standard)
+                                .map(KioskEntry::new)
+                                .collect(),
+                        })
+                        .filter(|klasse| !klasse.entries.is_empty())
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Only the entries on `page` (starting at 1), if each page has room for `rows` entries.
+    /// Days without any changes are kept on every page.
+    pub(crate) fn page(&self, page: usize, rows: usize) -> KioskFeed {
+        let rows = rows.max(1);
+        let total = self
+            .days
+            .iter()
+            .flat_map(|day| &day.classes)
+            .map(|klasse| klasse.entries.len())
+            .sum::<usize>();
+        let pages = total.div_ceil(rows).max(1);
+        let page = page.clamp(1, pages);
+        let visible = (page - 1) * rows..page * rows;
+
+        let mut index = 0;
+        let mut days = vec![];
+        for day in &self.days {
+            let mut classes = vec![];
+            for klasse in &day.classes {
+                let start = index;
+                index += klasse.entries.len();
+                let entries = klasse
+                    .entries
+                    .iter()
+                    .enumerate()
+                    .filter(|(offset, _)| visible.contains(&(start + offset)))
+                    .map(|(_, entry)| entry.clone())
+                    .collect::<Vec<KioskEntry>>();
+                if !entries.is_empty() {
+                    classes.push(KioskClass {
+                        name: klasse.name.to_string(),
+                        entries,
+                    });
+                }
+            }
+            if !classes.is_empty() || day.classes.is_empty() {
+                days.push(KioskDay {
+                    date: day.date,
+                    classes,
+                });
+            }
+        }
+        KioskFeed {
+            updated: self.updated,
+            page,
+            pages,
+            days,
+        }
+    }
+}
+
+/// The kiosk feed, shared by all screens and refreshed at most every few minutes
+#[derive(Default)]
+pub(crate) struct Kiosk {
+    pub(crate) feed: rocket::tokio::sync::Mutex<Option<KioskFeed>>,
+}
+
+impl Kiosk {
+    const MAX_AGE_MINUTES: i64 = 5;
+
+    pub(crate) async fn feed(&self, user: &UsernamePassword) -> anyhow::Result<KioskFeed> {
+        // Held while fetching, so that screens refreshing at the same time wait for one fetch
+        let mut feed = self.feed.lock().await;
+        let now = chrono::Local::now().naive_local();
+        if let Some(feed) = feed.as_ref().filter(|feed| {
+            feed.updated.date() == now.date()
+                && now - feed.updated < chrono::Duration::minutes(Self::MAX_AGE_MINUTES)
+        }) {
+            return Ok(feed.clone());
+        }
+
+        let today = now.date();
+        let next = school_calendar(user)
+            .await
+            .next_school_day(today)
+            .unwrap_or(today + chrono::Days::new(1));
+        let dates = [today, next];
+        let timetables = fetch_klassen_timetables(user, &dates).await?;
+        Ok(feed.insert(KioskFeed::new(timetables, &dates)).clone())
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct KioskPageDay<'a> {
+    pub(crate) name: String,
+    pub(crate) classes: &'a [KioskClass],
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct KioskPage<'a> {
+    pub(crate) lang: &'static str,
+    pub(crate) t: HashMap<String, String>,
+    /// Seconds until the next page is shown
+    pub(crate) seconds: u64,
+    pub(crate) next: String,
+    pub(crate) pages: String,
+    updated: String,
+    pub(crate) days: Vec<KioskPageDay<'a>>,
+}
+
+/// JSON feed of the changes of all classes today and tomorrow. Without `page`, all entries are
+/// returned.
+#[get("/kiosk/feed?<page>&<rows>")]
+pub(crate) async fn kiosk_feed(
+    page: Option<usize>,
+    rows: Option<usize>,
+    kiosk: &State<Kiosk>,
+) -> Result<Json<KioskFeed>, Status> {
+    let user = kiosk_account().ok_or(Status::NotFound)?;
+    let feed = kiosk.feed(&user).await.map_err(|err| {
+        error!("Could not retrieve kiosk feed: {err:#}");
+        Status::BadGateway
+    })?;
+    Ok(Json(match page {
+        Some(page) => feed.page(page, rows.unwrap_or(12)),
+        None => feed,
+    }))
+}
+
+/// Full-screen page for hallway screens, cycling through the pages of the feed every
+/// `seconds`
+#[get("/kiosk?<page>&<rows>&<seconds>")]
+pub(crate) async fn kiosk_page(
+    page: Option<usize>,
+    rows: Option<usize>,
+    seconds: Option<u64>,
+    kiosk: &State<Kiosk>,
+    language: Language,
+) -> Result<RawHtml<String>, Status> {
+    let user = kiosk_account().ok_or(Status::NotFound)?;
+    let rows = rows.unwrap_or(12);
+    let seconds = seconds.unwrap_or(20).max(5);
+    let feed = kiosk.feed(&user).await.map_err(|err| {
+        error!("Could not retrieve kiosk feed: {err:#}");
+        Status::BadGateway
+    })?;
+    let feed = feed.page(page.unwrap_or(1), rows);
+
+    let mut texts = html_texts(language)
+        .into_iter()
+        .map(|(name, text)| (name.to_string(), text))
+        .collect::<HashMap<String, String>>();
+    for (name, id) in [
+        ("kiosk_title", "kiosk-title"),
+        ("kiosk_class", "kiosk-class"),
+        ("kiosk_period", "kiosk-period"),
+        ("kiosk_subject", "kiosk-subject"),
+        ("kiosk_teacher", "kiosk-teacher"),
+        ("kiosk_room", "kiosk-room"),
+        ("kiosk_info", "kiosk-info"),
+        ("kiosk_no_changes", "kiosk-no-changes"),
+    ] {
+        texts.insert(name.to_string(), language.translate(id, &[]));
+    }
+    for state in SpeechTemplates::STATES
+        .iter()
+        .chain([&PeriodState::Unknown(String::new())])
+    {
+        let name = state.template_name();
+        texts.insert(
+            format!("state_{name}"),
+            language.translate(&format!("state-{}", name.replace('_', "-")), &[]),
+        );
+    }
+
+    let today = chrono::Local::now().date_naive();
+    render_html(
+        "kiosk.html",
+        KioskPage {
+            lang: language.tag(),
+            t: texts,
+            seconds,
+            next: format!(
+                "/kiosk?page={}&rows={rows}&seconds={seconds}",
+                feed.page % feed.pages + 1
+            ),
+            pages: language.translate(
+                "kiosk-page",
+                &[
+                    ("page", &feed.page.to_string()),
+                    ("pages", &feed.pages.to_string()),
+                ],
+            ),
+            updated: language.translate(
+                "kiosk-updated",
+                &[("time", &language.format_time(feed.updated.time()))],
+            ),
+            days: feed
+                .days
+                .iter()
+                .map(|day| KioskPageDay {
+                    name: format!(
+                        "{}, {}",
+                        if day.date == today {
+                            language.translate("day-today", &[])
+                        } else if Some(day.date) == today.succ_opt() {
+                            language.translate("day-tomorrow", &[])
+                        } else {
+                            language.translate(weekday_message(day.date.weekday()), &[])
+                        },
+                        language.format_date(day.date)
+                    ),
+                    classes: &day.classes,
+                })
+                .collect(),
+        },
+    )
+}
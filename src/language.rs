@@ -0,0 +1,164 @@
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource};
+use rocket::request;
+use rocket::request::{FromRequest, Request};
+use std::sync::OnceLock;
+use unic_langid::LanguageIdentifier;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Language {
+    German,
+    English,
+}
+
+impl Language {
+    /// Matches a language tag like `en`, `en-GB` or `de_DE` against the supported languages
+    pub(crate) fn from_tag(tag: &str) -> Option<Self> {
+        let language: LanguageIdentifier = tag.trim().replace('_', "-").parse().ok()?;
+        match language.language.as_str() {
+            "de" => Some(Language::German),
+            "en" => Some(Language::English),
+            _ => None,
+        }
+    }
+
+    /// Picks the first supported language from an `Accept-Language` header, by quality
+    fn from_accept_language(header: &str) -> Option<Self> {
+        let mut languages = header
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';');
+                let language = Language::from_tag(parts.next()?)?;
+                let quality = parts
+                    .find_map(|part| part.trim().strip_prefix("q="))
+                    .and_then(|quality| quality.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                Some((language, quality))
+            })
+            .collect::<Vec<(Language, f32)>>();
+        languages.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        languages.first().map(|(language, _)| *language)
+    }
+
+    fn bundle(&self) -> &'static FluentBundle<FluentResource> {
+        static GERMAN: OnceLock<FluentBundle<FluentResource>> = OnceLock::new();
+        static ENGLISH: OnceLock<FluentBundle<FluentResource>> = OnceLock::new();
+
+        let (cell, tag, source) = match self {
+            Language::German => (&GERMAN, "de-DE", include_str!("locales/de.ftl")),
+            Language::English => (&ENGLISH, "en-GB", include_str!("locales/en.ftl")),
+        };
+        cell.get_or_init(|| {
+            let resource = FluentResource::try_new(source.to_string())
+                .expect("Could not parse bundled translations!");
+            let mut bundle = FluentBundle::new_concurrent(vec![tag.parse().unwrap()]);
+            // Unicode isolation marks would end up in the speech output
+            bundle.set_use_isolating(false);
+            bundle
+                .add_resource(resource)
+                .expect("Duplicate message in bundled translations!");
+            bundle
+        })
+    }
+
+    /// Looks up the message `id` and fills in the given arguments
+    pub(crate) fn translate(&self, id: &str, args: &[(&str, &str)]) -> String {
+        self.format_message(id, None, None, args)
+    }
+
+    /// Like [`Language::translate`], but uses the given attribute of the message, if it has one
+    pub(crate) fn translate_attribute(
+        &self,
+        id: &str,
+        attribute: &str,
+        args: &[(&str, &str)],
+    ) -> String {
+        self.format_message(id, Some(attribute), None, args)
+    }
+
+    /// Like [`Language::translate`], but passes `count` as number, so the message can choose a
+    /// plural form
+    pub(crate) fn translate_count(&self, id: &str, count: usize, args: &[(&str, &str)]) -> String {
+        self.format_message(id, None, Some(count), args)
+    }
+
+    fn format_message(
+        &self,
+        id: &str,
+        attribute: Option<&str>,
+        count: Option<usize>,
+        args: &[(&str, &str)],
+    ) -> String {
+        let bundle = self.bundle();
+        let Some(message) = bundle.get_message(id) else {
+            error!("Message '{id}' missing in translations for {self:?}");
+            return id.to_string();
+        };
+        let Some(pattern) = attribute
+            .and_then(|attribute| message.get_attribute(attribute))
+            .map(|attribute| attribute.value())
+            .or(message.value())
+        else {
+            error!("Message '{id}' has no value in translations for {self:?}");
+            return id.to_string();
+        };
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args {
+            fluent_args.set(*name, value.to_string());
+        }
+        if let Some(count) = count {
+            fluent_args.set("count", count);
+        }
+        let mut errors = vec![];
+        let out = bundle
+            .format_pattern(pattern, Some(&fluent_args), &mut errors)
+            .to_string();
+        if !errors.is_empty() {
+            error!("Could not format message '{id}' for {self:?}: {errors:?}");
+        }
+        out
+    }
+
+    pub(crate) fn tag(&self) -> &'static str {
+        match self {
+            Language::German => "de",
+            Language::English => "en",
+        }
+    }
+
+    pub(crate) fn format_date(&self, date: chrono::NaiveDate) -> String {
+        match self {
+            Language::German => date.format("%d.%m.%Y").to_string(),
+            Language::English => date.format("%-d %b %Y").to_string(),
+        }
+    }
+
+    pub(crate) fn format_time(&self, time: chrono::NaiveTime) -> String {
+        match self {
+            Language::German => time.format("%H:%M").to_string(),
+            Language::English => time.format("%-I:%M %p").to_string(),
+        }
+    }
+}
+
+/// Chooses the language from the `lang` query parameter, the `Accept-Language` header, or falls
+/// back to German
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Language {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let language = request
+            .query_value::<&str>("lang")
+            .and_then(|lang| lang.ok())
+            .and_then(Language::from_tag)
+            .or_else(|| {
+                request
+                    .headers()
+                    .get_one("Accept-Language")
+                    .and_then(Language::from_accept_language)
+            })
+            .unwrap_or(Language::German);
+        request::Outcome::Success(language)
+    }
+}
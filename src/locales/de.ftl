@@ -0,0 +1,163 @@
+conjunction-and = und
+
+time-range = zwischen { $start } und { $end } Uhr
+time-period = in der { $first }. Stunde
+time-double-period = in der { $first }. und { $last }. Stunde
+time-period-range = von der { $first }. bis zur { $last }. Stunde
+
+period-cancel = { $subject } fällt { $time } aus!
+    .ssml = { $subject } <emphasis level="strong">fällt</emphasis> { $time } <emphasis level="strong">aus</emphasis>!
+period-standard = Im Fach { $subject } gibt es { $time } keine Änderungen!
+period-changed = Änderung bei { $subject } { $time }: { $changes }
+period-additional = '{ $text }' findet { $time } als Event statt.
+period-substitution-text = Zusätzliche Information zu { $subject } { $time }: { $text }.
+
+klasse-substituted = Klasse '{ $name }' statt '{ $original }'
+klasse-added = Klasse '{ $name }' zusätzlich
+klasse-absent = Klasse '{ $name }' fehlt
+klasse-regular = Klasse '{ $name }' bleibt
+
+teacher-substituted = '{ $name }' statt '{ $original }'
+teacher-added = '{ $name }' zusätzlich
+teacher-absent-original = Unterricht ohne Lehrer (von '{ $original }')
+teacher-absent = '{ $name }' fehlt
+teacher-regular = '{ $name }' bleibt
+
+room-substituted = Raumwechsel von '{ $original }' zu '{ $name }'
+room-added = Raum '{ $name }' zusätzlich
+room-absent-original = Unterricht ohne Raum (von '{ $original }')
+room-absent = Raum '{ $name }' fehlt
+room-regular = Raum '{ $name }' bleibt
+
+day-today = Heute
+day-tomorrow = Morgen
+day-weekday = Am { $weekday }
+day-weekday-date = Am { $weekday }, dem { $date }
+
+weekday-mon = Montag
+weekday-tue = Dienstag
+weekday-wed = Mittwoch
+weekday-thu = Donnerstag
+weekday-fri = Freitag
+weekday-sat = Samstag
+weekday-sun = Sonntag
+
+holiday-range = { $day } sind { $name } bis { $end }.
+holiday-day = { $day } ist { $name }, es findet kein Unterricht statt.
+school-resumes = { $day } ist wieder Unterricht.
+
+exam = { $day } { $subject }-{ $type } { $time }.
+exam-untyped = { $day } Prüfung in { $subject } { $time }.
+exams-none = In den nächsten { $days } Tagen stehen keine Prüfungen an.
+exam-reminder-title = { $count ->
+    [one] Prüfung morgen
+   *[other] Prüfung in { $count } Tagen
+}
+
+homework = Für { $due }: { $subject } Hausaufgabe '{ $text }'.
+homework-due-tomorrow = morgen
+homework-due-weekday = { $weekday }
+homework-due-date = { $weekday }, den { $date }
+homework-no-subject = Unterricht
+homework-reminder-title = { $count ->
+    [one] Eine Hausaufgabe für { $due }
+   *[other] { $count } Hausaufgaben für { $due }
+}
+
+absence = { $day } { $time } als abwesend eingetragen, { $status }.
+absence-days = Vom { $start } bis { $end } als abwesend eingetragen, { $status }.
+absence-reason = Grund: { $reason }.
+absence-notification-title = Neue Fehlzeit
+
+day-message = Nachricht zum Tag: { $subject }. { $text }
+day-message-untitled = Nachricht zum Tag: { $text }
+excuse-status-excused = entschuldigt
+excuse-status-unexcused = nicht entschuldigt
+excuse-status-open = noch nicht entschuldigt
+
+date-day-month = { $day }. { $month }
+month-1 = Januar
+month-2 = Februar
+month-3 = März
+month-4 = April
+month-5 = Mai
+month-6 = Juni
+month-7 = Juli
+month-8 = August
+month-9 = September
+month-10 = Oktober
+month-11 = November
+month-12 = Dezember
+
+assistant-no-changes = { $day } gibt es keine Änderungen.
+assistant-link-account = Bitte verknüpfe zuerst dein WebUntis-Konto in der App.
+assistant-unknown-intent = Das habe ich leider nicht verstanden. Frag mich zum Beispiel nach den Änderungen für morgen.
+assistant-error = Der Stundenplan konnte gerade nicht abgerufen werden. Bitte versuche es später noch einmal.
+assistant-goodbye = Tschüss!
+
+summary = { $day } { $changes }; { $times }
+summary-cancellations = { $count ->
+    [one] ein Ausfall
+   *[other] { $count } Ausfälle
+}
+summary-substitutions = { $count ->
+    [one] eine Vertretung
+   *[other] { $count } Vertretungen
+}
+summary-room-changes = { $count ->
+    [one] ein Raumwechsel
+   *[other] { $count } Raumwechsel
+}
+summary-additional = { $count ->
+    [one] eine zusätzliche Veranstaltung
+   *[other] { $count } zusätzliche Veranstaltungen
+}
+summary-no-changes = keine Änderungen
+summary-start = Unterricht beginnt um { $start }
+summary-start-changed = Unterricht beginnt um { $start } statt { $original }
+summary-end = endet um { $end }
+summary-end-changed = endet um { $end } statt { $original }
+summary-times = { $start } und { $end }
+summary-all-cancelled = der gesamte Unterricht fällt aus
+summary-no-lessons = { $day } ist kein Unterricht.
+
+dashboard-title = Stundenplan
+dashboard-week = KW { $week }: { $start } – { $end }
+dashboard-previous = ← Vorherige Woche
+dashboard-current = Diese Woche
+dashboard-next = Nächste Woche →
+dashboard-logout = Abmelden
+dashboard-no-lessons = Kein Unterricht
+dashboard-error = Der Stundenplan konnte gerade nicht abgerufen werden.
+dashboard-login = Anmelden
+dashboard-username = Benutzername
+dashboard-password = Passwort
+dashboard-login-failed = Die Anmeldung bei WebUntis ist fehlgeschlagen.
+dashboard-teacher = Lehrer: { $name }
+dashboard-teacher-changed = Lehrer: { $name } statt { $original }
+dashboard-teacher-absent = Lehrer: { $original } fehlt
+dashboard-room = Raum: { $name }
+dashboard-room-changed = Raum: { $name } statt { $original }
+dashboard-room-absent = Raum: { $original } fehlt
+
+link-title = WebUntis verknüpfen
+link-submit = Verknüpfen
+
+state-standard = Unterricht
+state-substitution = Vertretung
+state-room-substitution = Raumänderung
+state-cancel = Entfall
+state-additional = Zusätzliche Veranstaltung
+state-substitution-text = Hinweis
+state-unknown = Geändert
+
+kiosk-title = Vertretungsplan
+kiosk-class = Klasse
+kiosk-period = Stunde
+kiosk-subject = Fach
+kiosk-teacher = Lehrer
+kiosk-room = Raum
+kiosk-info = Hinweis
+kiosk-no-changes = Keine Änderungen
+kiosk-page = Seite { $page } von { $pages }
+kiosk-updated = Stand: { $time } Uhr
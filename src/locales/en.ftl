@@ -0,0 +1,163 @@
+conjunction-and = and
+
+time-range = between { $start } and { $end }
+time-period = in period { $first }
+time-double-period = in periods { $first } and { $last }
+time-period-range = from period { $first } to { $last }
+
+period-cancel = { $subject } is cancelled { $time }!
+    .ssml = { $subject } <emphasis level="strong">is cancelled</emphasis> { $time }!
+period-standard = There are no changes in { $subject } { $time }!
+period-changed = Change in { $subject } { $time }: { $changes }
+period-additional = '{ $text }' takes place as an event { $time }.
+period-substitution-text = Additional information on { $subject } { $time }: { $text }.
+
+klasse-substituted = class '{ $name }' instead of '{ $original }'
+klasse-added = additionally class '{ $name }'
+klasse-absent = class '{ $name }' is absent
+klasse-regular = class '{ $name }' stays
+
+teacher-substituted = '{ $name }' instead of '{ $original }'
+teacher-added = additionally '{ $name }'
+teacher-absent-original = lesson without a teacher (instead of '{ $original }')
+teacher-absent = '{ $name }' is absent
+teacher-regular = '{ $name }' stays
+
+room-substituted = room change from '{ $original }' to '{ $name }'
+room-added = additionally room '{ $name }'
+room-absent-original = lesson without a room (instead of '{ $original }')
+room-absent = room '{ $name }' is missing
+room-regular = room '{ $name }' stays
+
+day-today = Today
+day-tomorrow = Tomorrow
+day-weekday = On { $weekday }
+day-weekday-date = On { $weekday }, { $date }
+
+weekday-mon = Monday
+weekday-tue = Tuesday
+weekday-wed = Wednesday
+weekday-thu = Thursday
+weekday-fri = Friday
+weekday-sat = Saturday
+weekday-sun = Sunday
+
+holiday-range = { $day } there are no lessons: { $name } until { $end }.
+holiday-day = { $day } there are no lessons: { $name }.
+school-resumes = { $day } lessons resume.
+
+exam = { $day } there is a { $subject } { $type } { $time }.
+exam-untyped = { $day } there is an exam in { $subject } { $time }.
+exams-none = There are no exams in the next { $days } days.
+exam-reminder-title = { $count ->
+    [one] Exam tomorrow
+   *[other] Exam in { $count } days
+}
+
+homework = For { $due }: { $subject } homework '{ $text }'.
+homework-due-tomorrow = tomorrow
+homework-due-weekday = { $weekday }
+homework-due-date = { $weekday }, { $date }
+homework-no-subject = lesson
+homework-reminder-title = { $count ->
+    [one] One homework for { $due }
+   *[other] { $count } homework assignments for { $due }
+}
+
+absence = { $day } { $time } recorded as absent, { $status }.
+absence-days = From { $start } to { $end } recorded as absent, { $status }.
+absence-reason = Reason: { $reason }.
+absence-notification-title = New absence
+
+day-message = Message of the day: { $subject }. { $text }
+day-message-untitled = Message of the day: { $text }
+excuse-status-excused = excused
+excuse-status-unexcused = not excused
+excuse-status-open = not excused yet
+
+date-day-month = { $day } { $month }
+month-1 = January
+month-2 = February
+month-3 = March
+month-4 = April
+month-5 = May
+month-6 = June
+month-7 = July
+month-8 = August
+month-9 = September
+month-10 = October
+month-11 = November
+month-12 = December
+
+assistant-no-changes = { $day } there are no changes.
+assistant-link-account = Please link your WebUntis account in the app first.
+assistant-unknown-intent = Sorry, I did not understand that. Ask me for example about the changes for tomorrow.
+assistant-error = The timetable could not be retrieved right now. Please try again later.
+assistant-goodbye = Goodbye!
+
+summary = { $day } { $changes }; { $times }
+summary-cancellations = { $count ->
+    [one] one cancellation
+   *[other] { $count } cancellations
+}
+summary-substitutions = { $count ->
+    [one] one substitution
+   *[other] { $count } substitutions
+}
+summary-room-changes = { $count ->
+    [one] one room change
+   *[other] { $count } room changes
+}
+summary-additional = { $count ->
+    [one] one additional event
+   *[other] { $count } additional events
+}
+summary-no-changes = no changes
+summary-start = lessons start at { $start }
+summary-start-changed = lessons start at { $start } instead of { $original }
+summary-end = end at { $end }
+summary-end-changed = end at { $end } instead of { $original }
+summary-times = { $start } and { $end }
+summary-all-cancelled = all lessons are cancelled
+summary-no-lessons = { $day } there are no lessons.
+
+dashboard-title = Timetable
+dashboard-week = Week { $week }: { $start } – { $end }
+dashboard-previous = ← Previous week
+dashboard-current = This week
+dashboard-next = Next week →
+dashboard-logout = Log out
+dashboard-no-lessons = No lessons
+dashboard-error = The timetable could not be retrieved right now.
+dashboard-login = Log in
+dashboard-username = Username
+dashboard-password = Password
+dashboard-login-failed = Logging in to WebUntis failed.
+dashboard-teacher = Teacher: { $name }
+dashboard-teacher-changed = Teacher: { $name } instead of { $original }
+dashboard-teacher-absent = Teacher: { $original } is absent
+dashboard-room = Room: { $name }
+dashboard-room-changed = Room: { $name } instead of { $original }
+dashboard-room-absent = Room: { $original } is unavailable
+
+link-title = Link WebUntis
+link-submit = Link
+
+state-standard = Lesson
+state-substitution = Substitution
+state-room-substitution = Room change
+state-cancel = Cancelled
+state-additional = Additional event
+state-substitution-text = Note
+state-unknown = Changed
+
+kiosk-title = Substitution plan
+kiosk-class = Class
+kiosk-period = Period
+kiosk-subject = Subject
+kiosk-teacher = Teacher
+kiosk-room = Room
+kiosk-info = Note
+kiosk-no-changes = No changes
+kiosk-page = Page { $page } of { $pages }
+kiosk-updated = Updated at { $time }
@@ -0,0 +1,508 @@
+use anyhow::anyhow;
+use chrono::Timelike;
+use reqwest::Client;
+use rocket::serde::json::serde_json::{self, json};
+use std::collections::{BTreeSet, HashMap};
+
+use crate::config_dir;
+use crate::language::Language;
+use crate::speech::{speakable_day_name, Lexicon, Speech, SpeechFormat};
+use crate::untis::absences::{fetch_absences, Absence, ExcuseStatus};
+use crate::untis::calendar::school_calendar;
+use crate::untis::exams::fetch_exams;
+use crate::untis::homework::{fetch_homework, Homework};
+use crate::untis::messages::{fetch_inbox, MessageSummary};
+use crate::untis::{build_client, UsernamePassword};
+
+/// A service notifications are pushed to
+#[derive(Debug)]
+pub(crate) enum NotificationBackend {
+    /// `POST`s `{"title": .., "message": ..}` to the URL
+    Webhook(String),
+    /// Publishes to an ntfy topic, given as `https://ntfy.sh/<topic>`
+    Ntfy(String),
+}
+
+/// Sends notifications, e.g. reminders, to all backends configured with `UNTIS_NOTIFY_WEBHOOK`
+/// and `UNTIS_NOTIFY_NTFY`
+#[derive(Debug)]
+pub(crate) struct Notifier {
+    pub(crate) backends: Vec<NotificationBackend>,
+}
+
+impl Notifier {
+    pub(crate) fn from_env() -> Self {
+        let mut backends = vec![];
+        if let Ok(url) = std::env::var("UNTIS_NOTIFY_WEBHOOK") {
+            backends.push(NotificationBackend::Webhook(url));
+        }
+        if let Ok(url) = std::env::var("UNTIS_NOTIFY_NTFY") {
+            backends.push(NotificationBackend::Ntfy(url));
+        }
+        Notifier { backends }
+    }
+
+    /// Sends the notification to every backend. Succeeds, if at least one backend received it, so
+    /// that it is not sent to those again; failures of the other backends are logged. Fails with
+    /// the errors of all backends, if none received it.
+    pub(crate) async fn notify(&self, title: &str, message: &str) -> anyhow::Result<()> {
+        let client = build_client();
+        let mut errors = vec![];
+        for backend in &self.backends {
+            match Self::send(&client, backend, title, message).await {
+                Ok(()) => debug!("Sent notification to {backend:?}"),
+                Err(err) => {
+                    warn!("Could not send notification to {backend:?}: {err:#}");
+                    errors.push(format!("{backend:?}: {err:#}"));
+                }
+            }
+        }
+        if !self.backends.is_empty() && errors.len() == self.backends.len() {
+            return Err(anyhow!(
+                "Could not send notification to any backend ({})",
+                errors.join("; ")
+            ));
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn send(
+        client: &Client,
+        backend: &NotificationBackend,
+        title: &str,
+        message: &str,
+    ) -> anyhow::Result<()> {
+        let request = match backend {
+            NotificationBackend::Webhook(url) => client
+                .post(url)
+                .json(&json!({ "title": title, "message": message })),
+            NotificationBackend::Ntfy(url) => {
+                let (server, topic) = url
+                    .trim_end_matches('/')
+                    .rsplit_once('/')
+                    .ok_or(anyhow!("'{url}' is not an ntfy topic URL"))?;
+                client.post(server).json(&json!({
+                    "topic": topic,
+                    "title": title,
+                    "message": message,
+                }))
+            }
+        };
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// When reminders were last sent, by kind of reminder (or `exam-<id>` for single exams).
+/// Persisted in `<config dir>/reminders.json`, so that a restart does not send them twice. A log
+/// that cannot be read is reported and treated as empty, so that reminders are rather sent twice
+/// than not at all.
+fn reminder_log() -> HashMap<String, chrono::NaiveDate> {
+    let path = config_dir().join("reminders.json");
+    let log = match std::fs::read_to_string(&path) {
+        Ok(log) => log,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return HashMap::new(),
+        Err(err) => {
+            error!("Could not read {}: {err}", path.display());
+            return HashMap::new();
+        }
+    };
+    serde_json::from_str(&log).unwrap_or_else(|err| {
+        error!("Could not parse {}: {err}", path.display());
+        HashMap::new()
+    })
+}
+
+fn reminder_sent(kind: &str, date: chrono::NaiveDate) -> bool {
+    reminder_log().get(kind).is_some_and(|sent| *sent >= date)
+}
+
+fn set_reminder_sent(kind: &str, date: chrono::NaiveDate) -> anyhow::Result<()> {
+    let mut log = reminder_log();
+    log.insert(kind.to_string(), date);
+    write_atomically(
+        &config_dir().join("reminders.json"),
+        &serde_json::to_string_pretty(&log)?,
+    )
+}
+
+/// Writes `contents` to a temporary file next to `path` first and then renames it, so that a
+/// crash while writing never leaves a truncated file behind
+fn write_atomically(path: &std::path::Path, contents: &str) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    std::fs::write(&temp_path, contents)?;
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// Account and language reminders are sent for. Reminders are disabled without
+/// `UNTIS_REMINDER_USERNAME` and `UNTIS_REMINDER_PASSWORD`.
+pub(crate) fn reminder_account() -> Option<(UsernamePassword, Language)> {
+    Some((
+        UsernamePassword {
+            username: std::env::var("UNTIS_REMINDER_USERNAME").ok()?,
+            password: std::env::var("UNTIS_REMINDER_PASSWORD").ok()?,
+        },
+        std::env::var("UNTIS_REMINDER_LANGUAGE")
+            .ok()
+            .and_then(|tag| Language::from_tag(&tag))
+            .unwrap_or(Language::German),
+    ))
+}
+
+/// Reminders are sent once a day, from this hour on
+const REMINDER_HOUR: u32 = 17;
+
+/// How many days in advance exams are reminded of, e.g. `7,1` (default: `1`)
+fn exam_reminder_days() -> Vec<u64> {
+    std::env::var("UNTIS_EXAM_REMINDER_DAYS")
+        .unwrap_or_else(|_| String::from("1"))
+        .split(',')
+        .filter_map(|days| days.trim().parse().ok())
+        .collect()
+}
+
+async fn send_exam_reminders(
+    user: &UsernamePassword,
+    language: Language,
+    notifier: &Notifier,
+    today: chrono::NaiveDate,
+) -> anyhow::Result<()> {
+    let days = exam_reminder_days();
+    let Some(max_days) = days.iter().max() else {
+        return Ok(());
+    };
+    let to = today
+        .checked_add_days(chrono::Days::new(*max_days))
+        .ok_or(anyhow!("Invalid 'UNTIS_EXAM_REMINDER_DAYS' {max_days}"))?;
+    let exams = fetch_exams(user, today, to).await?;
+    let lexicon = Lexicon::load()?;
+    let speech = Speech {
+        language,
+        format: SpeechFormat::Text,
+        period_numbers: true,
+        lexicon: &lexicon,
+        account: &user.username,
+    };
+    for exam in exams {
+        let until = (exam.date - today).num_days() as u64;
+        // Recorded per exam, so that a failure halfway does not repeat the ones already sent
+        let kind = format!("exam-{}", exam.id);
+        if !days.contains(&until) || reminder_sent(&kind, today) {
+            continue;
+        }
+        info!("Sending reminder for exam {} on {}...", exam.id, exam.date);
+        notifier
+            .notify(
+                &speech.translate_count("exam-reminder-title", until as usize, &[]),
+                &exam.speakable(speech, today),
+            )
+            .await?;
+        set_reminder_sent(&kind, today)?;
+    }
+    Ok(())
+}
+
+/// Whether open homework is reminded of on the school day before it is due
+/// (`UNTIS_HOMEWORK_REMINDER`)
+fn homework_reminder() -> bool {
+    std::env::var("UNTIS_HOMEWORK_REMINDER")
+        .map(|reminder| reminder.eq_ignore_ascii_case("true") || reminder == "1")
+        .unwrap_or(false)
+}
+
+async fn send_homework_reminder(
+    user: &UsernamePassword,
+    language: Language,
+    notifier: &Notifier,
+    today: chrono::NaiveDate,
+) -> anyhow::Result<()> {
+    // Sent on school days only, so that the reminder for Monday is sent on Friday, not again on
+    // the weekend
+    let calendar = school_calendar(user).await;
+    if !calendar.is_school_day(today) {
+        return Ok(());
+    }
+    let Some(due) = calendar.next_school_day(today) else {
+        return Ok(());
+    };
+    let homework = fetch_homework(user, due, due)
+        .await?
+        .into_iter()
+        .filter(|homework| !homework.completed)
+        .collect::<Vec<Homework>>();
+    if homework.is_empty() {
+        return Ok(());
+    }
+    let lexicon = Lexicon::load()?;
+    let speech = Speech {
+        language,
+        format: SpeechFormat::Text,
+        period_numbers: true,
+        lexicon: &lexicon,
+        account: &user.username,
+    };
+    info!("Sending reminder for {} homework...", homework.len());
+    notifier
+        .notify(
+            &speech.translate_count(
+                "homework-reminder-title",
+                homework.len(),
+                &[("due", &Homework::speakable_due(speech, due, today))],
+            ),
+            &homework
+                .iter()
+                .map(|homework| homework.speakable(speech, today))
+                .collect::<Vec<String>>()
+                .join("\n"),
+        )
+        .await
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Reminder {
+    Exams,
+    Homework,
+}
+
+impl Reminder {
+    const ALL: [Reminder; 2] = [Reminder::Exams, Reminder::Homework];
+
+    /// Key in `reminders.json`
+    fn kind(&self) -> &'static str {
+        match self {
+            Reminder::Exams => "exams",
+            Reminder::Homework => "homework",
+        }
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        match self {
+            Reminder::Exams => !exam_reminder_days().is_empty(),
+            Reminder::Homework => homework_reminder(),
+        }
+    }
+
+    pub(crate) async fn send(
+        &self,
+        user: &UsernamePassword,
+        language: Language,
+        notifier: &Notifier,
+        today: chrono::NaiveDate,
+    ) -> anyhow::Result<()> {
+        match self {
+            Reminder::Exams => send_exam_reminders(user, language, notifier, today).await,
+            Reminder::Homework => send_homework_reminder(user, language, notifier, today).await,
+        }
+    }
+}
+
+/// Whether new absences without an excuse are notified (`UNTIS_ABSENCE_NOTIFICATION`)
+fn absence_notification() -> bool {
+    std::env::var("UNTIS_ABSENCE_NOTIFICATION")
+        .map(|notification| notification.eq_ignore_ascii_case("true") || notification == "1")
+        .unwrap_or(false)
+}
+
+/// How often the absences are checked for new entries
+const ABSENCE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// How far back new absences are looked for, since they are often entered late
+const ABSENCE_NOTIFICATION_DAYS: u64 = 30;
+
+/// The ids of all absences or messages already notified of, persisted in
+/// `<config dir>/<kind>.json`. `None`, if they have never been checked. Fails, if the file cannot
+/// be read, instead of treating everything as new (or as already known) again.
+fn notified_ids(kind: &str) -> anyhow::Result<Option<BTreeSet<u64>>> {
+    let path = config_dir().join(format!("{kind}.json"));
+    let ids = match std::fs::read_to_string(&path) {
+        Ok(ids) => ids,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(anyhow!("Could not read {}: {err}", path.display())),
+    };
+    serde_json::from_str(&ids)
+        .map(Some)
+        .map_err(|err| anyhow!("Could not parse {}: {err}", path.display()))
+}
+
+fn set_notified_ids(kind: &str, ids: &BTreeSet<u64>) -> anyhow::Result<()> {
+    write_atomically(
+        &config_dir().join(format!("{kind}.json")),
+        &serde_json::to_string(ids)?,
+    )
+}
+
+impl Absence {
+    /// "Am Freitag, dem 16. Oktober in der 1. Stunde als abwesend eingetragen, noch nicht
+    /// entschuldigt. Grund: Krankheit."
+    pub(crate) fn speakable(&self, speech: Speech, today: chrono::NaiveDate) -> String {
+        let status = speech.translate(
+            match self.excuse_status {
+                ExcuseStatus::Excused => "excuse-status-excused",
+                ExcuseStatus::Unexcused => "excuse-status-unexcused",
+                ExcuseStatus::Open => "excuse-status-open",
+            },
+            &[],
+        );
+        let mut sentences = vec![if self.start_date == self.end_date {
+            speech.translate(
+                "absence",
+                &[
+                    ("day", &speakable_day_name(speech, self.start_date, today)),
+                    (
+                        "time",
+                        &speech.time_span(self.start_time, self.end_time, self.period_numbers),
+                    ),
+                    ("status", &status),
+                ],
+            )
+        } else {
+            speech.translate(
+                "absence-days",
+                &[
+                    ("start", &speech.date(self.start_date)),
+                    ("end", &speech.date(self.end_date)),
+                    ("status", &status),
+                ],
+            )
+        }];
+        if !self.reason.is_empty() {
+            sentences.push(
+                speech.translate("absence-reason", &[("reason", &speech.text(&self.reason))]),
+            );
+        }
+        sentences.join(" ")
+    }
+}
+
+/// Notifies of absences without an excuse, that were not notified of before. On the first check,
+/// existing absences are only remembered.
+async fn notify_new_absences(
+    user: &UsernamePassword,
+    language: Language,
+    notifier: &Notifier,
+    today: chrono::NaiveDate,
+) -> anyhow::Result<()> {
+    let absences = fetch_absences(
+        user,
+        today - chrono::Days::new(ABSENCE_NOTIFICATION_DAYS),
+        today,
+    )
+    .await?
+    .into_iter()
+    .filter(|absence| absence.excuse_status != ExcuseStatus::Excused)
+    .collect::<Vec<Absence>>();
+    let Some(mut notified) = notified_ids("absences")? else {
+        info!("Remembering {} existing absences...", absences.len());
+        return set_notified_ids(
+            "absences",
+            &absences.iter().map(|absence| absence.id).collect(),
+        );
+    };
+    let lexicon = Lexicon::load()?;
+    let speech = Speech {
+        language,
+        format: SpeechFormat::Text,
+        period_numbers: true,
+        lexicon: &lexicon,
+        account: &user.username,
+    };
+    for absence in absences {
+        if notified.contains(&absence.id) {
+            continue;
+        }
+        info!("Sending notification for absence {}...", absence.id);
+        notifier
+            .notify(
+                &speech.translate("absence-notification-title", &[]),
+                &absence.speakable(speech, today),
+            )
+            .await?;
+        notified.insert(absence.id);
+        set_notified_ids("absences", &notified)?;
+    }
+    Ok(())
+}
+
+/// Whether new messages are forwarded (`UNTIS_MESSAGE_NOTIFICATION`)
+fn message_notification() -> bool {
+    std::env::var("UNTIS_MESSAGE_NOTIFICATION")
+        .map(|notification| notification.eq_ignore_ascii_case("true") || notification == "1")
+        .unwrap_or(false)
+}
+
+/// Forwards unread messages, that were not forwarded before. On the first check, existing
+/// messages are only remembered.
+async fn notify_new_messages(user: &UsernamePassword, notifier: &Notifier) -> anyhow::Result<()> {
+    let messages = fetch_inbox(user)
+        .await?
+        .messages
+        .into_iter()
+        .filter(|message| !message.read)
+        .collect::<Vec<MessageSummary>>();
+    let Some(mut notified) = notified_ids("messages")? else {
+        info!("Remembering {} existing messages...", messages.len());
+        return set_notified_ids(
+            "messages",
+            &messages.iter().map(|message| message.id).collect(),
+        );
+    };
+    for message in messages {
+        if notified.contains(&message.id) {
+            continue;
+        }
+        info!("Forwarding message {}...", message.id);
+        let text = match message.sender {
+            Some(sender) => format!("{sender}: {}", message.preview),
+            None => message.preview,
+        };
+        notifier.notify(&message.subject, &text).await?;
+        notified.insert(message.id);
+        set_notified_ids("messages", &notified)?;
+    }
+    Ok(())
+}
+
+/// Checks every few minutes for new messages and whether today's reminders are due, and every
+/// hour for new absences
+pub(crate) async fn reminder_loop(user: UsernamePassword, language: Language, notifier: Notifier) {
+    let mut absences_checked: Option<std::time::Instant> = None;
+    loop {
+        let now = chrono::Local::now().naive_local();
+        let today = now.date();
+        if absence_notification()
+            && absences_checked.is_none_or(|checked| checked.elapsed() >= ABSENCE_CHECK_INTERVAL)
+        {
+            if let Err(err) = notify_new_absences(&user, language, &notifier, today).await {
+                error!("Could not check for new absences: {err:#}");
+            }
+            absences_checked = Some(std::time::Instant::now());
+        }
+        if message_notification() {
+            if let Err(err) = notify_new_messages(&user, &notifier).await {
+                error!("Could not check for new messages: {err:#}");
+            }
+        }
+        for reminder in Reminder::ALL {
+            if now.hour() < REMINDER_HOUR
+                || !reminder.enabled()
+                || reminder_sent(reminder.kind(), today)
+            {
+                continue;
+            }
+            match reminder.send(&user, language, &notifier, today).await {
+                Ok(()) => {
+                    if let Err(err) = set_reminder_sent(reminder.kind(), today) {
+                        error!("Could not save sent reminders: {err:#}");
+                    }
+                }
+                Err(err) => error!("Could not send {} reminders: {err:#}", reminder.kind()),
+            }
+        }
+        rocket::tokio::time::sleep(std::time::Duration::from_secs(10 * 60)).await;
+    }
+}
@@ -0,0 +1,657 @@
+pub(crate) mod templates;
+pub(crate) mod tts;
+
+use anyhow::anyhow;
+use chrono::Datelike;
+use rocket::http::ContentType;
+use rocket::serde::json::serde_json;
+use rocket::serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use crate::assistant::weekday_message;
+use crate::config_dir;
+use crate::language::Language;
+use crate::speech::templates::SpeechTemplates;
+use crate::untis::calendar::Holiday;
+use crate::untis::timegrid::PeriodNumbers;
+use crate::untis::timetable::{ElementState, Klasse, Period, PeriodState, Room, Teacher};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum SpeechFormat {
+    Text,
+    Ssml,
+}
+
+impl SpeechFormat {
+    pub(crate) fn from_name(name: Option<&str>) -> Self {
+        match name {
+            Some(name) if name.eq_ignore_ascii_case("ssml") => SpeechFormat::Ssml,
+            _ => SpeechFormat::Text,
+        }
+    }
+}
+
+/// Escapes text for use inside of SSML (or any other XML document)
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Spoken forms of names and abbreviations, that text-to-speech engines would otherwise garble,
+/// e.g. "Ev. Religion" or "NW2-H". Kept per school in `<config dir>/lexicon/<school>.json`.
+#[derive(Debug)]
+pub(crate) struct Lexicon {
+    pub(crate) path: PathBuf,
+    pub(crate) entries: RwLock<BTreeMap<String, String>>,
+}
+
+impl Lexicon {
+    pub(crate) fn load() -> anyhow::Result<Self> {
+        let school =
+            std::env::var("UNTIS_SCHOOL").map_err(|_| anyhow!("'UNTIS_SCHOOL' not defined!"))?;
+        let path = config_dir()
+            .join("lexicon")
+            .join(format!("{}.json", school.replace(['/', '\\'], "_")));
+        let entries = if path.exists() {
+            info!("Loading pronunciation lexicon {}...", path.display());
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Lexicon {
+            path,
+            entries: RwLock::new(entries),
+        })
+    }
+
+    pub(crate) fn entries(&self) -> BTreeMap<String, String> {
+        self.entries.read().unwrap().clone()
+    }
+
+    /// The spoken form of `name`. Entries matching exactly take precedence over those only
+    /// differing in case.
+    pub(crate) fn lookup(&self, name: &str) -> Option<String> {
+        let entries = self.entries.read().unwrap();
+        entries
+            .get(name)
+            .or_else(|| {
+                entries
+                    .iter()
+                    .find(|(written, _)| written.to_lowercase() == name.to_lowercase())
+                    .map(|(_, spoken)| spoken)
+            })
+            .cloned()
+    }
+
+    pub(crate) fn insert(&self, name: &str, spoken: &str) -> anyhow::Result<()> {
+        let mut entries = self.entries.write().unwrap();
+        entries.insert(name.to_string(), spoken.to_string());
+        self.save(&entries)
+    }
+
+    /// Removes the entry and returns whether there was one
+    pub(crate) fn remove(&self, name: &str) -> anyhow::Result<bool> {
+        let mut entries = self.entries.write().unwrap();
+        if entries.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.save(&entries)?;
+        Ok(true)
+    }
+
+    pub(crate) fn save(&self, entries: &BTreeMap<String, String>) -> anyhow::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(entries)?)?;
+        Ok(())
+    }
+}
+
+/// Full surnames of teachers by their abbreviation, retrieved with `getTeachers`, per WebUntis
+/// account. `None` if the account is not permitted to look up teachers.
+#[allow(clippy::type_complexity)]
+pub(crate) static TEACHER_SURNAMES: RwLock<
+    Option<HashMap<String, Option<HashMap<String, String>>>>,
+> = RwLock::new(None);
+
+/// Whether teacher abbreviations are spoken as their surnames. Enabled with
+/// `UNTIS_LOOKUP_TEACHER_NAMES=true`; most schools only permit this for teacher accounts.
+pub(crate) fn lookup_teacher_names() -> bool {
+    std::env::var("UNTIS_LOOKUP_TEACHER_NAMES")
+        .map(|lookup| lookup.eq_ignore_ascii_case("true") || lookup == "1")
+        .unwrap_or(false)
+}
+
+/// How speakable output is produced: the language of the sentences, and whether they are plain
+/// text or SSML markup. All values passed into a sentence go through one of the methods here, so
+/// they are escaped and annotated correctly.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Speech<'a> {
+    pub(crate) language: Language,
+    pub(crate) format: SpeechFormat,
+    /// Say "in der 3. Stunde" instead of "zwischen 09:45 und 10:30 Uhr", if possible
+    pub(crate) period_numbers: bool,
+    pub(crate) lexicon: &'a Lexicon,
+    /// WebUntis account the output is for, teachers are only spoken with the surnames this
+    /// account may see
+    pub(crate) account: &'a str,
+}
+
+impl Speech<'_> {
+    pub(crate) fn translate(&self, id: &str, args: &[(&str, &str)]) -> String {
+        match self.format {
+            SpeechFormat::Text => self.language.translate(id, args),
+            SpeechFormat::Ssml => self.language.translate_attribute(id, "ssml", args),
+        }
+    }
+
+    pub(crate) fn translate_count(&self, id: &str, count: usize, args: &[(&str, &str)]) -> String {
+        self.language.translate_count(id, count, args)
+    }
+
+    pub(crate) fn text(&self, text: &str) -> String {
+        match self.format {
+            SpeechFormat::Text => text.to_string(),
+            SpeechFormat::Ssml => escape_xml(text),
+        }
+    }
+
+    /// A name, that is written differently than it is spoken, e.g. an abbreviation. The lexicon
+    /// takes precedence over the spoken form WebUntis provides.
+    pub(crate) fn name(&self, written: &str, spoken: &str) -> String {
+        let spoken = self
+            .lexicon
+            .lookup(written)
+            .or_else(|| self.lexicon.lookup(spoken))
+            .unwrap_or_else(|| spoken.to_string());
+        let spoken = spoken.as_str();
+        match self.format {
+            SpeechFormat::Text => spoken.to_string(),
+            SpeechFormat::Ssml if written == spoken || written.is_empty() => escape_xml(spoken),
+            SpeechFormat::Ssml => format!(
+                "<sub alias=\"{}\">{}</sub>",
+                escape_xml(spoken),
+                escape_xml(written)
+            ),
+        }
+    }
+
+    /// A date as day and month, e.g. "25. Oktober"
+    pub(crate) fn date(&self, date: chrono::NaiveDate) -> String {
+        self.translate(
+            "date-day-month",
+            &[
+                ("day", &date.day().to_string()),
+                (
+                    "month",
+                    &self.translate(&format!("month-{}", date.month()), &[]),
+                ),
+            ],
+        )
+    }
+
+    /// A teacher's abbreviation, spoken as their surname, if it is known
+    pub(crate) fn teacher(&self, name: &str) -> String {
+        let surname = TEACHER_SURNAMES
+            .read()
+            .unwrap()
+            .as_ref()
+            .and_then(|accounts| accounts.get(self.account))
+            .and_then(|surnames| surnames.as_ref())
+            .and_then(|surnames| surnames.get(name).cloned());
+        self.name(name, surname.as_deref().unwrap_or(name))
+    }
+
+    pub(crate) fn time(&self, time: chrono::NaiveTime) -> String {
+        let formatted = self.language.format_time(time);
+        match (self.format, self.language) {
+            (SpeechFormat::Text, _) => formatted,
+            (SpeechFormat::Ssml, Language::German) => {
+                format!("<say-as interpret-as=\"time\" format=\"hms24\">{formatted}</say-as>")
+            }
+            (SpeechFormat::Ssml, Language::English) => {
+                format!("<say-as interpret-as=\"time\" format=\"hms12\">{formatted}</say-as>")
+            }
+        }
+    }
+
+    /// When the period takes place, by its period numbers or its start and end time
+    pub(crate) fn period_time(&self, period: &Period) -> String {
+        self.time_span(period.start_time, period.end_time, period.period_numbers)
+    }
+
+    /// Period numbers, if enabled and known, or start and end time
+    pub(crate) fn time_span(
+        &self,
+        start: chrono::NaiveTime,
+        end: chrono::NaiveTime,
+        numbers: Option<PeriodNumbers>,
+    ) -> String {
+        match (self.period_numbers, numbers) {
+            (true, Some(PeriodNumbers { first, last })) if first == last => {
+                self.translate("time-period", &[("first", &first.to_string())])
+            }
+            (true, Some(PeriodNumbers { first, last })) if last == first + 1 => self.translate(
+                "time-double-period",
+                &[("first", &first.to_string()), ("last", &last.to_string())],
+            ),
+            (true, Some(PeriodNumbers { first, last })) => self.translate(
+                "time-period-range",
+                &[("first", &first.to_string()), ("last", &last.to_string())],
+            ),
+            _ => self.translate(
+                "time-range",
+                &[("start", &self.time(start)), ("end", &self.time(end))],
+            ),
+        }
+    }
+
+    /// Joins the speakable texts of multiple periods into one response
+    pub(crate) fn join(&self, sentences: Vec<String>) -> String {
+        match self.format {
+            SpeechFormat::Text => sentences.join("\n"),
+            SpeechFormat::Ssml => format!(
+                "<speak>{}</speak>",
+                sentences
+                    .into_iter()
+                    .map(|sentence| format!("<s>{sentence}</s>"))
+                    .collect::<Vec<String>>()
+                    .join("<break time=\"700ms\"/>")
+            ),
+        }
+    }
+
+    pub(crate) fn content_type(&self) -> ContentType {
+        match self.format {
+            SpeechFormat::Text => ContentType::Plain,
+            SpeechFormat::Ssml => ContentType::new("application", "ssml+xml"),
+        }
+    }
+}
+
+impl Klasse {
+    fn speakable_change(&self, speech: Speech) -> String {
+        let name = speech.name(&self.name, &self.long_name);
+        match (&self.state, &self.original_klasse) {
+            (ElementState::Substituted, Some(original_klasse)) => speech.translate(
+                "klasse-substituted",
+                &[
+                    ("name", &name),
+                    (
+                        "original",
+                        &speech.name(&original_klasse.name, &original_klasse.long_name),
+                    ),
+                ],
+            ),
+            (ElementState::Substituted, None) => {
+                speech.translate("klasse-added", &[("name", &name)])
+            }
+            (ElementState::Absent, _) => speech.translate("klasse-absent", &[("name", &name)]),
+            (ElementState::Regular | ElementState::Unknown(_), _) => {
+                speech.translate("klasse-regular", &[("name", &name)])
+            }
+        }
+    }
+}
+
+impl Teacher {
+    fn speakable_change(&self, speech: Speech) -> String {
+        let name = speech.teacher(&self.name);
+        match (&self.state, &self.original_teacher) {
+            (ElementState::Substituted, Some(original_teacher)) => speech.translate(
+                "teacher-substituted",
+                &[
+                    ("name", &name),
+                    ("original", &speech.teacher(&original_teacher.name)),
+                ],
+            ),
+            (ElementState::Substituted, None) => {
+                speech.translate("teacher-added", &[("name", &name)])
+            }
+            (ElementState::Absent, Some(original_teacher)) => speech.translate(
+                "teacher-absent-original",
+                &[("original", &speech.teacher(&original_teacher.name))],
+            ),
+            (ElementState::Absent, None) => speech.translate("teacher-absent", &[("name", &name)]),
+            (ElementState::Regular | ElementState::Unknown(_), _) => {
+                speech.translate("teacher-regular", &[("name", &name)])
+            }
+        }
+    }
+}
+
+impl Room {
+    fn speakable_change(&self, speech: Speech) -> String {
+        let name = speech.name(&self.name, &self.long_name);
+        match (&self.state, &self.original_room) {
+            (ElementState::Substituted, Some(original_room)) => speech.translate(
+                "room-substituted",
+                &[
+                    ("name", &name),
+                    (
+                        "original",
+                        &speech.name(&original_room.name, &original_room.long_name),
+                    ),
+                ],
+            ),
+            (ElementState::Substituted, None) => speech.translate("room-added", &[("name", &name)]),
+            (ElementState::Absent, Some(original_room)) => speech.translate(
+                "room-absent-original",
+                &[(
+                    "original",
+                    &speech.name(&original_room.name, &original_room.long_name),
+                )],
+            ),
+            (ElementState::Absent, None) => speech.translate("room-absent", &[("name", &name)]),
+            (ElementState::Regular | ElementState::Unknown(_), _) => {
+                speech.translate("room-regular", &[("name", &name)])
+            }
+        }
+    }
+}
+
+/// Joins the descriptions of a group of elements (e.g. all teachers of a period), if at least
+/// one of them has changed. Unchanged elements are only mentioned alongside changed ones.
+fn speakable_changes(changes: Vec<(&ElementState, String)>) -> Option<String> {
+    if changes
+        .iter()
+        .all(|(state, _)| matches!(state, ElementState::Regular | ElementState::Unknown(_)))
+    {
+        return None;
+    }
+    Some(
+        changes
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect::<Vec<String>>()
+            .join(", "),
+    )
+}
+
+impl Period {
+    pub(crate) fn subject_name(&self, speech: Speech) -> Option<String> {
+        if self.subjects.is_empty() {
+            return None;
+        }
+        Some(
+            self.subjects
+                .iter()
+                .map(|subject| speech.name(&subject.name, &subject.long_name))
+                .collect::<Vec<String>>()
+                .join(&format!(" {} ", speech.translate("conjunction-and", &[]))),
+        )
+    }
+
+    pub(crate) fn speakable_klassen_changes(&self, speech: Speech) -> Option<String> {
+        speakable_changes(
+            self.klassen
+                .iter()
+                .map(|klasse| (&klasse.state, klasse.speakable_change(speech)))
+                .collect(),
+        )
+    }
+
+    pub(crate) fn speakable_teacher_changes(&self, speech: Speech) -> Option<String> {
+        speakable_changes(
+            self.teachers
+                .iter()
+                .map(|teacher| (&teacher.state, teacher.speakable_change(speech)))
+                .collect(),
+        )
+    }
+
+    pub(crate) fn speakable_room_changes(&self, speech: Speech) -> Option<String> {
+        speakable_changes(
+            self.rooms
+                .iter()
+                .map(|room| (&room.state, room.speakable_change(speech)))
+                .collect(),
+        )
+    }
+
+    pub(crate) fn speakable_text(&self, speech: Speech) -> String {
+        let Some(subject) = self.subject_name(speech) else {
+            return String::new();
+        };
+        let time = speech.period_time(self);
+        let substitution_text = speech.text(&self.substitution_text);
+        match self.state {
+            PeriodState::Cancel => {
+                speech.translate("period-cancel", &[("subject", &subject), ("time", &time)])
+            }
+            PeriodState::Standard => {
+                speech.translate("period-standard", &[("subject", &subject), ("time", &time)])
+            }
+            PeriodState::Substitution | PeriodState::Unknown(_) => {
+                let mut changes = [
+                    self.speakable_teacher_changes(speech),
+                    self.speakable_room_changes(speech),
+                    self.speakable_klassen_changes(speech),
+                ]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<String>>();
+                changes.push(substitution_text);
+                speech.translate(
+                    "period-changed",
+                    &[
+                        ("subject", &subject),
+                        ("time", &time),
+                        ("changes", &changes.join("; ")),
+                    ],
+                )
+            }
+            PeriodState::RoomSubstitution => speech.translate(
+                "period-changed",
+                &[
+                    ("subject", &subject),
+                    ("time", &time),
+                    (
+                        "changes",
+                        &self.speakable_room_changes(speech).unwrap_or_default(),
+                    ),
+                ],
+            ),
+            PeriodState::Additional => speech.translate(
+                "period-additional",
+                &[("text", &substitution_text), ("time", &time)],
+            ),
+            PeriodState::SubstitutionText => speech.translate(
+                "period-substitution-text",
+                &[
+                    ("subject", &subject),
+                    ("time", &time),
+                    ("text", &substitution_text),
+                ],
+            ),
+        }
+    }
+}
+
+/// "Heute", "Morgen" or "Am Montag", as seen from `today`
+pub(crate) fn speakable_day_name(
+    speech: Speech,
+    date: chrono::NaiveDate,
+    today: chrono::NaiveDate,
+) -> String {
+    if date == today {
+        speech.translate("day-today", &[])
+    } else if date == today + chrono::Duration::days(1) {
+        speech.translate("day-tomorrow", &[])
+    } else if date < today || date > today + chrono::Duration::days(6) {
+        // The weekday alone would be ambiguous
+        speech.translate(
+            "day-weekday-date",
+            &[
+                (
+                    "weekday",
+                    &speech.translate(weekday_message(date.weekday()), &[]),
+                ),
+                ("date", &speech.date(date)),
+            ],
+        )
+    } else {
+        speech.translate(
+            "day-weekday",
+            &[(
+                "weekday",
+                &speech.translate(weekday_message(date.weekday()), &[]),
+            )],
+        )
+    }
+}
+
+/// "Morgen sind Herbstferien bis 25. Oktober."
+pub(crate) fn speakable_holiday(
+    speech: Speech,
+    holiday: &Holiday,
+    date: chrono::NaiveDate,
+    today: chrono::NaiveDate,
+) -> String {
+    let day = speakable_day_name(speech, date, today);
+    let name = speech.name(&holiday.name, &holiday.long_name);
+    if holiday.start == holiday.end {
+        speech.translate("holiday-day", &[("day", &day), ("name", &name)])
+    } else {
+        speech.translate(
+            "holiday-range",
+            &[
+                ("day", &day),
+                ("name", &name),
+                ("end", &speech.date(holiday.end)),
+            ],
+        )
+    }
+}
+
+/// Key figures of a single day: how many periods changed and when lessons start and end
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct DayOverview {
+    pub(crate) date: chrono::NaiveDate,
+    pub(crate) cancellations: usize,
+    pub(crate) substitutions: usize,
+    #[serde(rename = "roomChanges")]
+    pub(crate) room_changes: usize,
+    pub(crate) additional: usize,
+    /// When lessons would start without any changes
+    #[serde(rename = "regularStart")]
+    pub(crate) regular_start: Option<chrono::NaiveTime>,
+    #[serde(rename = "regularEnd")]
+    pub(crate) regular_end: Option<chrono::NaiveTime>,
+    /// When lessons start, taking cancelled and additional periods into account
+    pub(crate) start: Option<chrono::NaiveTime>,
+    pub(crate) end: Option<chrono::NaiveTime>,
+}
+
+impl DayOverview {
+    pub(crate) fn new(timetable: &[Period], date: chrono::NaiveDate) -> Self {
+        let periods = timetable
+            .iter()
+            .filter(|period| period.date == date)
+            .collect::<Vec<&Period>>();
+        let count = |state: PeriodState| {
+            periods
+                .iter()
+                .filter(|period| period.state == state)
+                .count()
+        };
+        let regular = periods
+            .iter()
+            .filter(|period| period.state != PeriodState::Additional);
+        let effective = periods
+            .iter()
+            .filter(|period| period.state != PeriodState::Cancel);
+        DayOverview {
+            date,
+            cancellations: count(PeriodState::Cancel),
+            substitutions: count(PeriodState::Substitution),
+            room_changes: count(PeriodState::RoomSubstitution),
+            additional: count(PeriodState::Additional),
+            regular_start: regular.clone().map(|period| period.start_time).min(),
+            regular_end: regular.map(|period| period.end_time).max(),
+            start: effective.clone().map(|period| period.start_time).min(),
+            end: effective.map(|period| period.end_time).max(),
+        }
+    }
+
+    /// E.g. "Heute 2 Ausfälle, 1 Vertretung; Unterricht beginnt um 09:45 statt 08:00 und endet
+    /// um 12:15"
+    pub(crate) fn speakable(&self, speech: Speech, today: chrono::NaiveDate) -> String {
+        let day = speakable_day_name(speech, self.date, today);
+        let (Some(regular_start), Some(regular_end)) = (self.regular_start, self.regular_end)
+        else {
+            return speech.translate("summary-no-lessons", &[("day", &day)]);
+        };
+
+        let changes = [
+            ("summary-cancellations", self.cancellations),
+            ("summary-substitutions", self.substitutions),
+            ("summary-room-changes", self.room_changes),
+            ("summary-additional", self.additional),
+        ]
+        .into_iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(id, count)| speech.translate_count(id, count, &[]))
+        .collect::<Vec<String>>();
+        let changes = if changes.is_empty() {
+            speech.translate("summary-no-changes", &[])
+        } else {
+            changes.join(", ")
+        };
+
+        let times = match (self.start, self.end) {
+            (Some(start), Some(end)) => {
+                let start = if start == regular_start {
+                    speech.translate("summary-start", &[("start", &speech.time(start))])
+                } else {
+                    speech.translate(
+                        "summary-start-changed",
+                        &[
+                            ("start", &speech.time(start)),
+                            ("original", &speech.time(regular_start)),
+                        ],
+                    )
+                };
+                let end = if end == regular_end {
+                    speech.translate("summary-end", &[("end", &speech.time(end))])
+                } else {
+                    speech.translate(
+                        "summary-end-changed",
+                        &[
+                            ("end", &speech.time(end)),
+                            ("original", &speech.time(regular_end)),
+                        ],
+                    )
+                };
+                speech.translate("summary-times", &[("start", &start), ("end", &end)])
+            }
+            _ => speech.translate("summary-all-cancelled", &[]),
+        };
+        speech.translate(
+            "summary",
+            &[("day", &day), ("changes", &changes), ("times", &times)],
+        )
+    }
+}
+
+/// The speakable texts of all changed periods on the given day
+pub(crate) fn speakable_day(
+    timetable: &[Period],
+    date: chrono::NaiveDate,
+    speech: Speech,
+    templates: &SpeechTemplates,
+) -> Vec<String> {
+    timetable
+        .iter()
+        .filter(|period| period.state != PeriodState::Standard)
+        .filter(|period| period.date == date)
+        .map(|period| templates.render(period, speech))
+        .collect()
+}
@@ -0,0 +1,225 @@
+use anyhow::anyhow;
+use rocket::serde::json::serde_json;
+use rocket::serde::Serialize;
+
+use crate::config_dir;
+use crate::language::Language;
+use crate::speech::Speech;
+use crate::untis::timetable::{ElementState, Period, PeriodState};
+
+impl PeriodState {
+    /// Name of the speech template used for periods in this state
+    pub(crate) fn template_name(&self) -> &'static str {
+        match self {
+            PeriodState::Standard => "standard",
+            PeriodState::Substitution => "substitution",
+            PeriodState::RoomSubstitution => "room_substitution",
+            PeriodState::Cancel => "cancel",
+            PeriodState::Additional => "additional",
+            PeriodState::SubstitutionText => "substitution_text",
+            PeriodState::Unknown(_) => "unknown",
+        }
+    }
+}
+
+/// Everything a speech template can refer to
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct SpeechContext<'a> {
+    pub(crate) lang: &'static str,
+    pub(crate) state: &'a PeriodState,
+    pub(crate) date: chrono::NaiveDate,
+    pub(crate) start: String,
+    pub(crate) end: String,
+    /// Start and end time or period numbers, as the built-in texts would say it
+    pub(crate) time: String,
+    pub(crate) first_period: Option<u32>,
+    pub(crate) last_period: Option<u32>,
+    pub(crate) subject: Option<String>,
+    pub(crate) teacher: Option<String>,
+    pub(crate) original_teacher: Option<String>,
+    pub(crate) room: Option<String>,
+    pub(crate) original_room: Option<String>,
+    pub(crate) lesson_text: String,
+    pub(crate) text: String,
+    pub(crate) info: String,
+    pub(crate) substitution_text: String,
+    /// The built-in description of all teacher, room and class changes
+    pub(crate) changes: Option<String>,
+    pub(crate) subjects: serde_json::Value,
+    pub(crate) teachers: serde_json::Value,
+    pub(crate) rooms: serde_json::Value,
+    pub(crate) klassen: serde_json::Value,
+}
+
+/// Passes every string in `value` through [`Speech::text`], so that the element lists can be
+/// used in templates as safely as the other values
+fn speech_values(value: impl Serialize, speech: Speech) -> serde_json::Value {
+    fn escape(value: serde_json::Value, speech: Speech) -> serde_json::Value {
+        match value {
+            serde_json::Value::String(text) => serde_json::Value::String(speech.text(&text)),
+            serde_json::Value::Array(values) => serde_json::Value::Array(
+                values
+                    .into_iter()
+                    .map(|value| escape(value, speech))
+                    .collect(),
+            ),
+            serde_json::Value::Object(values) => serde_json::Value::Object(
+                values
+                    .into_iter()
+                    .map(|(key, value)| (key, escape(value, speech)))
+                    .collect(),
+            ),
+            value => value,
+        }
+    }
+    escape(
+        serde_json::to_value(value).unwrap_or(serde_json::Value::Null),
+        speech,
+    )
+}
+
+impl<'a> SpeechContext<'a> {
+    pub(crate) fn new(period: &'a Period, speech: Speech) -> Self {
+        // Prefer the first changed element, as that is most likely the one worth mentioning
+        let teacher = period
+            .teachers
+            .iter()
+            .find(|teacher| teacher.state != ElementState::Regular)
+            .or(period.teachers.first());
+        let room = period
+            .rooms
+            .iter()
+            .find(|room| room.state != ElementState::Regular)
+            .or(period.rooms.first());
+        let changes = [
+            period.speakable_teacher_changes(speech),
+            period.speakable_room_changes(speech),
+            period.speakable_klassen_changes(speech),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<String>>();
+        SpeechContext {
+            lang: speech.language.tag(),
+            state: &period.state,
+            date: period.date,
+            start: speech.time(period.start_time),
+            end: speech.time(period.end_time),
+            time: speech.period_time(period),
+            first_period: period.period_numbers.map(|numbers| numbers.first),
+            last_period: period.period_numbers.map(|numbers| numbers.last),
+            subject: period.subject_name(speech),
+            teacher: teacher.map(|teacher| speech.teacher(&teacher.name)),
+            original_teacher: teacher
+                .and_then(|teacher| teacher.original_teacher.as_ref())
+                .map(|teacher| speech.teacher(&teacher.name)),
+            room: room.map(|room| speech.name(&room.name, &room.long_name)),
+            original_room: room
+                .and_then(|room| room.original_room.as_ref())
+                .map(|room| speech.name(&room.name, &room.long_name)),
+            lesson_text: speech.text(&period.lesson_text),
+            text: speech.text(&period.text),
+            info: speech.text(&period.info),
+            substitution_text: speech.text(&period.substitution_text),
+            changes: if changes.is_empty() {
+                None
+            } else {
+                Some(changes.join("; "))
+            },
+            subjects: speech_values(&period.subjects, speech),
+            teachers: speech_values(&period.teachers, speech),
+            rooms: speech_values(&period.rooms, speech),
+            klassen: speech_values(&period.klassen, speech),
+        }
+    }
+
+    /// Context with placeholder values, used to check the templates at startup
+    fn sample(state: &'a PeriodState) -> Self {
+        SpeechContext {
+            lang: Language::German.tag(),
+            state,
+            date: chrono::NaiveDate::default(),
+            start: String::from("08:00"),
+            end: String::from("08:45"),
+            time: String::from("zwischen 08:00 und 08:45 Uhr"),
+            first_period: Some(1),
+            last_period: Some(1),
+            subject: Some(String::from("Mathematik")),
+            teacher: Some(String::from("MUE")),
+            original_teacher: Some(String::from("SCH")),
+            room: Some(String::from("Raum 204")),
+            original_room: Some(String::from("Raum 105")),
+            lesson_text: String::new(),
+            text: String::new(),
+            info: String::new(),
+            substitution_text: String::new(),
+            changes: Some(String::from("'MUE' statt 'SCH'")),
+            subjects: serde_json::Value::Array(vec![]),
+            teachers: serde_json::Value::Array(vec![]),
+            rooms: serde_json::Value::Array(vec![]),
+            klassen: serde_json::Value::Array(vec![]),
+        }
+    }
+}
+
+/// User-defined speech templates, one per [`PeriodState`], loaded from
+/// `<config dir>/templates/<state>.jinja`. States without a template use the built-in texts.
+pub(crate) struct SpeechTemplates {
+    pub(crate) environment: minijinja::Environment<'static>,
+}
+
+impl SpeechTemplates {
+    pub(crate) const STATES: [PeriodState; 6] = [
+        PeriodState::Standard,
+        PeriodState::Substitution,
+        PeriodState::RoomSubstitution,
+        PeriodState::Cancel,
+        PeriodState::Additional,
+        PeriodState::SubstitutionText,
+    ];
+
+    /// Loads all templates and renders each of them once, so that mistakes show up at startup
+    /// rather than in the middle of a request
+    pub(crate) fn load() -> anyhow::Result<Self> {
+        let mut environment = minijinja::Environment::new();
+        environment.set_undefined_behavior(minijinja::UndefinedBehavior::Strict);
+        let directory = config_dir().join("templates");
+
+        let unknown = PeriodState::Unknown(String::new());
+        for state in Self::STATES.iter().chain([&unknown]) {
+            let name = state.template_name();
+            let path = directory.join(format!("{name}.jinja"));
+            if !path.exists() {
+                continue;
+            }
+            info!("Loading speech template {}...", path.display());
+            let source = std::fs::read_to_string(&path)?;
+            environment
+                .add_template_owned(name, source)
+                .map_err(|err| anyhow!("Invalid template {}: {err}", path.display()))?;
+            environment
+                .get_template(name)?
+                .render(SpeechContext::sample(state))
+                .map_err(|err| anyhow!("Could not render template {}: {err}", path.display()))?;
+        }
+        Ok(SpeechTemplates { environment })
+    }
+
+    /// Renders the template for the state of the period, or the built-in text, if there is none.
+    ///
+    /// For SSML output all values passed to the template are escaped (or contain markup), the
+    /// template itself is expected to be plain text.
+    pub(crate) fn render(&self, period: &Period, speech: Speech) -> String {
+        let Ok(template) = self.environment.get_template(period.state.template_name()) else {
+            return period.speakable_text(speech);
+        };
+        match template.render(SpeechContext::new(period, speech)) {
+            Ok(text) => text.trim().to_string(),
+            Err(err) => {
+                error!("Could not render speech template: {err:#}");
+                period.speakable_text(speech)
+            }
+        }
+    }
+}
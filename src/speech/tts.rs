@@ -0,0 +1,226 @@
+use anyhow::anyhow;
+use rocket::http::ContentType;
+use rocket::serde::uuid::Uuid;
+use std::path::PathBuf;
+
+use crate::config_dir;
+use crate::language::Language;
+use crate::speech::SpeechFormat;
+
+/// Audio container written by the text-to-speech engine
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum AudioFormat {
+    Wav,
+    Ogg,
+}
+
+impl AudioFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            AudioFormat::Wav => "wav",
+            AudioFormat::Ogg => "ogg",
+        }
+    }
+
+    pub(crate) fn content_type(&self) -> ContentType {
+        match self {
+            AudioFormat::Wav => ContentType::WAV,
+            AudioFormat::Ogg => ContentType::OGG,
+        }
+    }
+}
+
+/// A locally installed text-to-speech engine
+#[derive(Debug)]
+enum TtsEngine {
+    /// Program and arguments, reading the text from stdin and writing the audio to stdout.
+    /// `{lang}` in the arguments is replaced by the language tag.
+    Command(Vec<String>),
+    /// Produces an empty WAV file, for testing without an engine installed
+    Noop,
+}
+
+/// Number of audio files kept in the cache, older files are removed first
+const TTS_CACHE_FILES: usize = 500;
+
+/// Renders speakable texts to audio files and caches them in `<config dir>/tts-cache`, keyed by
+/// a hash of the engine command and the text
+#[derive(Debug)]
+pub(crate) struct TextToSpeech {
+    engine: TtsEngine,
+    pub(crate) format: AudioFormat,
+    pub(crate) input: SpeechFormat,
+    cache_dir: PathBuf,
+}
+
+impl TextToSpeech {
+    /// Configured with `UNTIS_TTS_COMMAND`, `UNTIS_TTS_AUDIO_FORMAT` and `UNTIS_TTS_INPUT`.
+    /// Returns `None`, if no engine is configured.
+    pub(crate) fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(command) = std::env::var("UNTIS_TTS_COMMAND") else {
+            return Ok(None);
+        };
+        let (engine, format) = if command.trim() == "noop" {
+            (TtsEngine::Noop, AudioFormat::Wav)
+        } else {
+            let arguments = command
+                .split_whitespace()
+                .map(String::from)
+                .collect::<Vec<String>>();
+            if arguments.is_empty() {
+                return Err(anyhow!("'UNTIS_TTS_COMMAND' is empty!"));
+            }
+            let format = match std::env::var("UNTIS_TTS_AUDIO_FORMAT")
+                .unwrap_or_else(|_| String::from("wav"))
+                .to_lowercase()
+                .as_str()
+            {
+                "wav" => AudioFormat::Wav,
+                "ogg" => AudioFormat::Ogg,
+                format => return Err(anyhow!("Unknown audio format '{format}'")),
+            };
+            (TtsEngine::Command(arguments), format)
+        };
+        let input = match std::env::var("UNTIS_TTS_INPUT").as_deref() {
+            Ok("ssml") => SpeechFormat::Ssml,
+            Ok("text") | Err(_) => SpeechFormat::Text,
+            Ok(input) => return Err(anyhow!("Unknown text-to-speech input '{input}'")),
+        };
+        Ok(Some(TextToSpeech {
+            engine,
+            format,
+            input,
+            cache_dir: config_dir().join("tts-cache"),
+        }))
+    }
+
+    pub(crate) fn arguments(&self, language: Language) -> Vec<String> {
+        match &self.engine {
+            TtsEngine::Command(arguments) => arguments
+                .iter()
+                .map(|argument| argument.replace("{lang}", language.tag()))
+                .collect(),
+            TtsEngine::Noop => vec![String::from("noop")],
+        }
+    }
+
+    /// Returns the cached audio of the text, or runs the engine and caches its output
+    pub(crate) async fn render(&self, text: &str, language: Language) -> anyhow::Result<Vec<u8>> {
+        let arguments = self.arguments(language);
+        let mut key = arguments.join(" ").into_bytes();
+        key.push(0);
+        key.extend_from_slice(text.as_bytes());
+        let hash = openssl::sha::sha256(&key)
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        let path = self
+            .cache_dir
+            .join(format!("{hash}.{}", self.format.extension()));
+        if let Ok(audio) = rocket::tokio::fs::read(&path).await {
+            debug!("Using cached audio {}", path.display());
+            return Ok(audio);
+        }
+
+        let audio = match self.engine {
+            TtsEngine::Noop => silent_wav(),
+            TtsEngine::Command(_) => {
+                let text = text.to_string();
+                rocket::tokio::task::spawn_blocking(move || run_tts_command(&arguments, &text))
+                    .await??
+            }
+        };
+        // Written to a temporary file first, so that concurrent requests for the same text never
+        // read a partially written file
+        rocket::tokio::fs::create_dir_all(&self.cache_dir).await?;
+        let temp_path = path.with_extension(format!("{}.tmp", Uuid::new_v4().simple()));
+        rocket::tokio::fs::write(&temp_path, &audio).await?;
+        rocket::tokio::fs::rename(&temp_path, &path).await?;
+        let cache_dir = self.cache_dir.clone();
+        if let Err(err) = rocket::tokio::task::spawn_blocking(move || Self::clean_cache(&cache_dir))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|cleaned| cleaned)
+        {
+            warn!("Could not clean up the audio cache: {err}");
+        }
+        Ok(audio)
+    }
+
+    /// Removes the oldest audio files beyond [`TTS_CACHE_FILES`]. Temporary files still being
+    /// written are left alone.
+    fn clean_cache(cache_dir: &std::path::Path) -> anyhow::Result<()> {
+        let mut files = std::fs::read_dir(cache_dir)?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_none_or(|extension| extension != "tmp"))
+            .filter_map(|path| Some((path.metadata().ok()?.modified().ok()?, path)))
+            .collect::<Vec<_>>();
+        if files.len() <= TTS_CACHE_FILES {
+            return Ok(());
+        }
+        files.sort();
+        for (_, path) in &files[..files.len() - TTS_CACHE_FILES] {
+            debug!("Removing cached audio {}", path.display());
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+fn run_tts_command(arguments: &[String], text: &str) -> anyhow::Result<Vec<u8>> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    debug!("Running text-to-speech engine {}...", arguments[0]);
+    let mut child = Command::new(&arguments[0])
+        .args(&arguments[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| anyhow!("Could not start '{}': {err}", arguments[0]))?;
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or(anyhow!("stdin of the text-to-speech engine not available"))?;
+    // Written from a separate thread, as engines may start writing audio before reading all of
+    // the text, which would block both sides once the pipe buffers are full
+    let text = text.to_string();
+    let writer = std::thread::spawn(move || stdin.write_all(text.as_bytes()));
+    let output = child.wait_with_output()?;
+    let written = writer
+        .join()
+        .map_err(|_| anyhow!("Writing to the text-to-speech engine panicked"))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "'{}' failed with {}: {}",
+            arguments[0],
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    written.map_err(|err| anyhow!("Could not pass the text to '{}': {err}", arguments[0]))?;
+    if output.stdout.is_empty() {
+        return Err(anyhow!("'{}' did not write any audio", arguments[0]));
+    }
+    Ok(output.stdout)
+}
+
+/// A WAV file without any samples (16 bit mono PCM at 22.05 kHz)
+fn silent_wav() -> Vec<u8> {
+    let mut wav = Vec::with_capacity(44);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&36u32.to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&22050u32.to_le_bytes());
+    wav.extend_from_slice(&44100u32.to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&0u32.to_le_bytes());
+    wav
+}
@@ -0,0 +1,965 @@
+use rocket::serde::json::serde_json::{self, json};
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use crate::kiosk::KioskFeed;
+use crate::language::Language;
+use crate::speech::{DayOverview, Speech, SpeechFormat};
+use crate::untis::absences::csv_field;
+use crate::untis::availability::{occupies_room, occupies_teacher, parse_period_numbers};
+use crate::untis::calendar::{Holiday, SchoolCalendar, SchoolYear};
+use crate::untis::exams::{escape_ics, fold_ics_line};
+use crate::untis::messages::html_text;
+use crate::untis::timegrid::{PeriodNumbers, Timegrid, TimegridDay};
+use crate::untis::timetable::{
+    merge_gap, merge_periods, parse_timetable, ElementState, ParseMode, Period, PeriodFlags,
+    PeriodState, TimetableElement,
+};
+use crate::Lexicon;
+
+#[test]
+fn escape_ics_escapes_special_characters() {
+    assert_eq!(
+        escape_ics("Mathe; Kapitel 3, 4\\5\nTaschenrechner\r\nmitbringen"),
+        r"Mathe\; Kapitel 3\, 4\\5\nTaschenrechner\nmitbringen"
+    );
+}
+
+#[test]
+fn fold_ics_line_keeps_short_lines() {
+    assert_eq!(fold_ics_line("SUMMARY:Mathe"), "SUMMARY:Mathe\r\n");
+}
+
+#[test]
+fn fold_ics_line_folds_after_75_octets() {
+    let line = format!("DESCRIPTION:{}", "a".repeat(100));
+    let folded = fold_ics_line(&line);
+    let lines = folded
+        .trim_end_matches("\r\n")
+        .split("\r\n")
+        .collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].len(), 75);
+    assert!(lines[1].starts_with(' '));
+    assert_eq!(folded.replace("\r\n ", ""), format!("{line}\r\n"));
+}
+
+#[test]
+fn fold_ics_line_does_not_split_characters() {
+    let line = format!("SUMMARY:{}", "ä".repeat(60));
+    let folded = fold_ics_line(&line);
+    for line in folded.trim_end_matches("\r\n").split("\r\n") {
+        assert!(line.len() <= 75, "{line:?} is {} octets", line.len());
+    }
+    assert_eq!(folded.replace("\r\n ", ""), format!("{line}\r\n"));
+}
+
+#[test]
+fn csv_field_quotes_only_if_needed() {
+    assert_eq!(csv_field("Krankheit"), "Krankheit");
+    assert_eq!(csv_field("Arzt, Zahnarzt"), "\"Arzt, Zahnarzt\"");
+    assert_eq!(csv_field("sagt \"krank\""), "\"sagt \"\"krank\"\"\"");
+    assert_eq!(csv_field("Zeile 1\nZeile 2"), "\"Zeile 1\nZeile 2\"");
+    assert_eq!(csv_field(""), "");
+}
+
+#[test]
+fn html_text_keeps_line_breaks() {
+    assert_eq!(
+        html_text("<p>Liebe Eltern,</p><p>morgen  ist<br/>Wandertag.</p>"),
+        "Liebe Eltern,\nmorgen ist\nWandertag."
+    );
+}
+
+#[test]
+fn html_text_decodes_entities() {
+    assert_eq!(
+        html_text(
+            "Gr&uuml;&szlig;e &Auml;&Ouml;&Uuml; &auml;&ouml; &amp;lt; &lt;b&gt; &quot;x&quot;"
+        ),
+        "Grüße ÄÖÜ äö &lt; <b> \"x\""
+    );
+    assert_eq!(html_text("&#228;&#xE4;&#XC4;&#39;"), "ääÄ'");
+    assert_eq!(
+        html_text("R&D &unknown; &#xZZ; &"),
+        "R&D &unknown; &#xZZ; &"
+    );
+}
+
+#[test]
+fn html_text_drops_styles_and_scripts() {
+    assert_eq!(
+        html_text("<STYLE>p { color: red; }</style><p>Text</p><script>alert('x')</SCRIPT>"),
+        "Text"
+    );
+    assert_eq!(html_text("vorher<script>nie geschlossen"), "vorher");
+}
+
+#[test]
+fn parse_period_numbers_accepts_numbers_and_ranges() {
+    assert_eq!(
+        parse_period_numbers("3"),
+        Some(PeriodNumbers { first: 3, last: 3 })
+    );
+    assert_eq!(
+        parse_period_numbers("3-4"),
+        Some(PeriodNumbers { first: 3, last: 4 })
+    );
+    assert_eq!(
+        parse_period_numbers(" 3 - 5 "),
+        Some(PeriodNumbers { first: 3, last: 5 })
+    );
+}
+
+#[test]
+fn parse_period_numbers_rejects_invalid_input() {
+    assert_eq!(parse_period_numbers(""), None);
+    assert_eq!(parse_period_numbers("drei"), None);
+    assert_eq!(parse_period_numbers("4-3"), None);
+    assert_eq!(parse_period_numbers("3-"), None);
+    assert_eq!(parse_period_numbers("-1"), None);
+}
+
+/// An element as listed in `elements` of the weekly timetable payload, with the fields of
+/// all element types
+fn weekly_element(element_type: u64, id: u64, name: &str) -> serde_json::Value {
+    json!({
+        "type": element_type,
+        "id": id,
+        "name": name,
+        "longName": format!("{name} (long)"),
+        "displayname": name,
+        "alternatename": "",
+        "backColor": "#f0e68c",
+        "canViewTimetable": true,
+        "externKey": "",
+        "roomCapacity": 30,
+    })
+}
+
+/// A reference from a period to an element, replacing `original_id` (0 for none)
+fn weekly_reference(
+    element_type: u64,
+    id: u64,
+    original_id: u64,
+    state: &str,
+) -> serde_json::Value {
+    json!({
+        "type": element_type,
+        "id": id,
+        "orgId": original_id,
+        "missing": false,
+        "state": state,
+    })
+}
+
+/// A period on 2025-03-17 (a Monday) from `start` to `end`, e.g. `800` for 08:00
+fn weekly_period(
+    id: u64,
+    start: u64,
+    end: u64,
+    cell_state: &str,
+    elements: Vec<serde_json::Value>,
+) -> serde_json::Value {
+    json!({
+        "id": id,
+        "lessonId": 100 + id,
+        "lessonCode": "UNTIS_LESSON",
+        "lessonText": "",
+        "periodText": "",
+        "periodInfo": "",
+        "substText": "",
+        "date": 20250317,
+        "startTime": start,
+        "endTime": end,
+        "elements": elements,
+        "cellState": cell_state,
+    })
+}
+
+/// The weekly timetable payload of the element with `element_id`
+fn weekly_timetable(
+    element_id: u64,
+    elements: Vec<serde_json::Value>,
+    periods: Vec<serde_json::Value>,
+) -> serde_json::Value {
+    let mut element_periods = serde_json::Map::new();
+    element_periods.insert(element_id.to_string(), periods.into());
+    json!({
+        "data": {
+            "result": {
+                "data": {
+                    "elements": elements,
+                    "elementPeriods": element_periods,
+                },
+            },
+        },
+    })
+}
+
+#[test]
+fn parse_timetable_resolves_classes() {
+    let timetable = weekly_timetable(
+        7,
+        vec![weekly_element(1, 7, "5a"), weekly_element(3, 20, "M")],
+        vec![weekly_period(
+            1,
+            800,
+            845,
+            "STANDARD",
+            vec![
+                weekly_reference(1, 7, 0, "REGULAR"),
+                weekly_reference(3, 20, 0, "REGULAR"),
+            ],
+        )],
+    );
+    let (periods, warnings) = parse_timetable(timetable, 7, ParseMode::Strict).unwrap();
+    assert!(warnings.is_empty());
+    assert_eq!(periods.len(), 1);
+    let klassen = &periods[0].klassen;
+    assert_eq!(klassen.len(), 1);
+    assert_eq!(klassen[0].id, 7);
+    assert_eq!(klassen[0].name, "5a");
+    assert_eq!(klassen[0].long_name, "5a (long)");
+    assert_eq!(klassen[0].state, ElementState::Regular);
+    assert!(klassen[0].original_klasse.is_none());
+    assert_eq!(periods[0].subjects[0].name, "M");
+}
+
+#[test]
+fn parse_timetable_resolves_replaced_classes() {
+    let timetable = weekly_timetable(
+        7,
+        vec![weekly_element(1, 7, "5a"), weekly_element(1, 8, "5b")],
+        vec![weekly_period(
+            1,
+            800,
+            845,
+            "SUBSTITUTION",
+            vec![weekly_reference(1, 7, 8, "SUBSTITUTED")],
+        )],
+    );
+    let (periods, _) = parse_timetable(timetable, 7, ParseMode::Strict).unwrap();
+    let klasse = &periods[0].klassen[0];
+    assert_eq!(klasse.state, ElementState::Substituted);
+    assert_eq!(klasse.original_klasse_id, 8);
+    assert_eq!(klasse.original_klasse.as_ref().unwrap().name, "5b");
+}
+
+#[test]
+fn parse_timetable_keeps_all_teachers_and_rooms() {
+    let timetable = weekly_timetable(
+        7,
+        vec![
+            weekly_element(1, 7, "5a"),
+            weekly_element(1, 9, "5c"),
+            weekly_element(2, 30, "MÜL"),
+            weekly_element(2, 31, "SCH"),
+            weekly_element(4, 40, "101"),
+            weekly_element(4, 41, "102"),
+        ],
+        vec![weekly_period(
+            1,
+            800,
+            845,
+            "STANDARD",
+            vec![
+                weekly_reference(1, 7, 0, "REGULAR"),
+                weekly_reference(1, 9, 0, "REGULAR"),
+                weekly_reference(2, 30, 0, "REGULAR"),
+                weekly_reference(2, 31, 0, "REGULAR"),
+                weekly_reference(4, 40, 0, "REGULAR"),
+                weekly_reference(4, 41, 0, "REGULAR"),
+            ],
+        )],
+    );
+    let (periods, _) = parse_timetable(timetable, 7, ParseMode::Strict).unwrap();
+    let period = &periods[0];
+    let names = |names: Vec<&str>| names.join(",");
+    assert_eq!(
+        names(period.klassen.iter().map(|k| k.name.as_str()).collect()),
+        "5a,5c"
+    );
+    assert_eq!(
+        names(period.teachers.iter().map(|t| t.name.as_str()).collect()),
+        "MÜL,SCH"
+    );
+    assert_eq!(
+        names(period.rooms.iter().map(|r| r.name.as_str()).collect()),
+        "101,102"
+    );
+}
+
+/// A timetable with an unknown cell state in the first period, and a second period without
+/// a date
+fn surprising_timetable() -> serde_json::Value {
+    let mut malformed = weekly_period(2, 855, 940, "STANDARD", vec![]);
+    malformed.as_object_mut().unwrap().remove("date");
+    weekly_timetable(
+        7,
+        vec![weekly_element(1, 7, "5a")],
+        vec![
+            weekly_period(
+                1,
+                800,
+                845,
+                "FLOATING",
+                vec![weekly_reference(1, 7, 0, "REGULAR")],
+            ),
+            malformed,
+        ],
+    )
+}
+
+#[test]
+fn parse_timetable_reports_warnings_with_paths_in_lenient_mode() {
+    let (periods, warnings) =
+        parse_timetable(surprising_timetable(), 7, ParseMode::Lenient).unwrap();
+    assert_eq!(periods.len(), 1);
+    assert_eq!(
+        periods[0].state,
+        PeriodState::Unknown(String::from("FLOATING"))
+    );
+    let paths = warnings
+        .iter()
+        .map(|warning| warning.path.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        [
+            ".data.result.data.elementPeriods.7[0].cellState",
+            ".data.result.data.elementPeriods.7[1]",
+        ]
+    );
+    assert!(
+        warnings[1].message.contains("date"),
+        "{}",
+        warnings[1].message
+    );
+}
+
+#[test]
+fn parse_timetable_fails_on_surprises_in_strict_mode() {
+    let err = parse_timetable(surprising_timetable(), 7, ParseMode::Strict).unwrap_err();
+    assert!(
+        format!("{err:#}").contains(".data.result.data.elementPeriods.7[0].cellState"),
+        "{err:#}"
+    );
+}
+
+#[test]
+fn parse_timetable_reports_missing_element_periods() {
+    let timetable = weekly_timetable(7, vec![], vec![]);
+    let (periods, warnings) = parse_timetable(timetable, 8, ParseMode::Lenient).unwrap();
+    assert!(periods.is_empty());
+    assert_eq!(warnings[0].path, ".data.result.data.elementPeriods.8");
+    let timetable = weekly_timetable(7, vec![], vec![]);
+    assert!(parse_timetable(timetable, 8, ParseMode::Strict).is_err());
+}
+
+#[test]
+fn parse_timetable_reads_the_typed_period_fields() {
+    let mut period = weekly_period(1, 745, 830, "ADDITIONAL", vec![]);
+    let fields = period.as_object_mut().unwrap();
+    fields.remove("lessonId");
+    fields.remove("lessonCode");
+    fields.insert(String::from("studentGroup"), json!("M_5a_1"));
+    fields.insert(String::from("periodText"), json!("Raum 101"));
+    fields.insert(String::from("hasPeriodText"), json!(true));
+    fields.insert(
+        String::from("is"),
+        json!({ "additional": true, "exam": true }),
+    );
+    let timetable = weekly_timetable(7, vec![], vec![period]);
+    let (periods, _) = parse_timetable(timetable, 7, ParseMode::Strict).unwrap();
+    let period = &periods[0];
+    assert_eq!(period.lesson_id, 0);
+    assert_eq!(period.lesson_code, "");
+    assert_eq!(period.student_group.as_deref(), Some("M_5a_1"));
+    assert_eq!(period.text, "Raum 101");
+    assert!(period.has_period_text);
+    assert_eq!(
+        period.date,
+        chrono::NaiveDate::from_ymd_opt(2025, 3, 17).unwrap()
+    );
+    assert_eq!(
+        period.start_time,
+        chrono::NaiveTime::from_hms_opt(7, 45, 0).unwrap()
+    );
+    assert_eq!(
+        period.end_time,
+        chrono::NaiveTime::from_hms_opt(8, 30, 0).unwrap()
+    );
+    assert_eq!(period.state, PeriodState::Additional);
+    assert_eq!(
+        period.is,
+        PeriodFlags {
+            additional: true,
+            exam: true,
+            ..PeriodFlags::default()
+        }
+    );
+}
+
+#[test]
+fn parse_timetable_rejects_other_payloads() {
+    let err =
+        parse_timetable(json!({ "data": { "error": "no" } }), 7, ParseMode::Lenient).unwrap_err();
+    assert!(
+        err.to_string().contains("not in the expected format"),
+        "{err}"
+    );
+}
+
+fn lexicon(entries: &[(&str, &str)]) -> Lexicon {
+    Lexicon {
+        path: PathBuf::new(),
+        entries: RwLock::new(
+            entries
+                .iter()
+                .map(|(written, spoken)| (written.to_string(), spoken.to_string()))
+                .collect(),
+        ),
+    }
+}
+
+fn speech(format: SpeechFormat, lexicon: &Lexicon) -> Speech<'_> {
+    Speech {
+        language: Language::German,
+        format,
+        period_numbers: false,
+        lexicon,
+        account: "test",
+    }
+}
+
+#[test]
+fn ssml_escapes_text() {
+    let lexicon = lexicon(&[]);
+    assert_eq!(
+        speech(SpeechFormat::Ssml, &lexicon).text("Bio & Chemie <Labor> \"A\" 'B'"),
+        "Bio &amp; Chemie &lt;Labor&gt; &quot;A&quot; &apos;B&apos;"
+    );
+    assert_eq!(
+        speech(SpeechFormat::Text, &lexicon).text("Bio & Chemie"),
+        "Bio & Chemie"
+    );
+}
+
+#[test]
+fn ssml_aliases_names_spoken_differently() {
+    let lexicon = lexicon(&[]);
+    let ssml = speech(SpeechFormat::Ssml, &lexicon);
+    assert_eq!(
+        ssml.name("D&E", "Deutsch & Englisch"),
+        "<sub alias=\"Deutsch &amp; Englisch\">D&amp;E</sub>"
+    );
+    assert_eq!(ssml.name("Mathe", "Mathe"), "Mathe");
+    assert_eq!(ssml.name("", "Mathe"), "Mathe");
+    assert_eq!(
+        speech(SpeechFormat::Text, &lexicon).name("D", "Deutsch"),
+        "Deutsch"
+    );
+}
+
+#[test]
+fn ssml_marks_times() {
+    let lexicon = lexicon(&[]);
+    let time = chrono::NaiveTime::from_hms_opt(9, 45, 0).unwrap();
+    let ssml = speech(SpeechFormat::Ssml, &lexicon).time(time);
+    assert!(
+        ssml.starts_with("<say-as interpret-as=\"time\" format=\"hms24\">"),
+        "{ssml}"
+    );
+    assert!(!speech(SpeechFormat::Text, &lexicon)
+        .time(time)
+        .contains('<'));
+}
+
+/// A time grid with five 45 minute periods on Mondays, the first one named "0"
+fn timegrid() -> Timegrid {
+    let days = serde_json::from_value::<Vec<TimegridDay>>(json!([{
+        "day": 2,
+        "timeUnits": [
+            { "name": "0", "startTime": 710, "endTime": 755 },
+            { "name": "1", "startTime": 800, "endTime": 845 },
+            { "name": "2", "startTime": 845, "endTime": 930 },
+            { "name": "3", "startTime": 950, "endTime": 1035 },
+            { "name": "Mittag", "startTime": 1035, "endTime": 1120 },
+        ],
+    }]))
+    .unwrap();
+    Timegrid::from_days(days).unwrap()
+}
+
+#[test]
+fn timegrid_numbers_single_and_double_periods() {
+    let timegrid = timegrid();
+    let monday = chrono::NaiveDate::from_ymd_opt(2025, 3, 17).unwrap();
+    let time = |hour, minute| chrono::NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+    assert_eq!(
+        timegrid.numbers(monday, time(8, 0), time(8, 45)),
+        Some(PeriodNumbers { first: 1, last: 1 })
+    );
+    assert_eq!(
+        timegrid.numbers(monday, time(8, 0), time(9, 30)),
+        Some(PeriodNumbers { first: 1, last: 2 })
+    );
+    // Periods shifted a little still count for the units they overlap with
+    assert_eq!(
+        timegrid.numbers(monday, time(8, 10), time(10, 0)),
+        Some(PeriodNumbers { first: 1, last: 3 })
+    );
+    assert_eq!(
+        timegrid.numbers(monday, time(7, 10), time(7, 55)),
+        Some(PeriodNumbers { first: 0, last: 0 })
+    );
+    // Unit names, that are no numbers, are counted instead
+    assert_eq!(
+        timegrid.numbers(monday, time(10, 40), time(11, 0)),
+        Some(PeriodNumbers { first: 5, last: 5 })
+    );
+}
+
+#[test]
+fn timegrid_has_no_numbers_outside_of_its_units() {
+    let timegrid = timegrid();
+    let monday = chrono::NaiveDate::from_ymd_opt(2025, 3, 17).unwrap();
+    let time = |hour, minute| chrono::NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+    assert_eq!(timegrid.numbers(monday, time(9, 30), time(9, 50)), None);
+    assert_eq!(timegrid.numbers(monday, time(14, 0), time(15, 0)), None);
+    let tuesday = monday.succ_opt().unwrap();
+    assert_eq!(timegrid.numbers(tuesday, time(8, 0), time(8, 45)), None);
+}
+
+#[test]
+fn timegrid_spans_period_numbers() {
+    let timegrid = timegrid();
+    let monday = chrono::NaiveDate::from_ymd_opt(2025, 3, 17).unwrap();
+    let time = |hour, minute| chrono::NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+    assert_eq!(
+        timegrid.span(monday, PeriodNumbers { first: 1, last: 2 }),
+        Some((time(8, 0), time(9, 30)))
+    );
+    assert_eq!(
+        timegrid.span(monday, PeriodNumbers { first: 3, last: 9 }),
+        None
+    );
+}
+
+/// The periods of a timetable of class 5a with one subject, teacher and room. `periods` are
+/// the start and end time, cell state, teacher and room of each period.
+fn class_periods(periods: &[(u64, u64, &str, u64, u64)]) -> Vec<Period> {
+    let elements = vec![
+        weekly_element(1, 7, "5a"),
+        weekly_element(2, 30, "MÜL"),
+        weekly_element(2, 31, "SCH"),
+        weekly_element(3, 20, "M"),
+        weekly_element(4, 40, "101"),
+        weekly_element(4, 41, "102"),
+    ];
+    let periods = periods
+        .iter()
+        .enumerate()
+        .map(|(index, (start, end, state, teacher, room))| {
+            weekly_period(
+                index as u64 + 1,
+                *start,
+                *end,
+                state,
+                vec![
+                    weekly_reference(1, 7, 0, "REGULAR"),
+                    weekly_reference(2, *teacher, 0, "REGULAR"),
+                    weekly_reference(3, 20, 0, "REGULAR"),
+                    weekly_reference(4, *room, 0, "REGULAR"),
+                ],
+            )
+        })
+        .collect();
+    parse_timetable(weekly_timetable(7, elements, periods), 7, ParseMode::Strict)
+        .unwrap()
+        .0
+}
+
+fn times(periods: &[Period]) -> Vec<(String, String)> {
+    periods
+        .iter()
+        .map(|period| {
+            (
+                period.start_time.format("%H:%M").to_string(),
+                period.end_time.format("%H:%M").to_string(),
+            )
+        })
+        .collect()
+}
+
+fn minutes(minutes: i64) -> chrono::Duration {
+    chrono::Duration::minutes(minutes)
+}
+
+#[test]
+fn merge_periods_merges_double_periods() {
+    let mut periods = class_periods(&[
+        (800, 845, "STANDARD", 30, 40),
+        (845, 930, "STANDARD", 30, 40),
+        (950, 1035, "STANDARD", 30, 40),
+    ]);
+    periods[0].period_numbers = Some(PeriodNumbers { first: 1, last: 1 });
+    periods[1].period_numbers = Some(PeriodNumbers { first: 2, last: 2 });
+    let merged = merge_periods(periods, minutes(5));
+    assert_eq!(
+        times(&merged),
+        [
+            (String::from("08:00"), String::from("09:30")),
+            (String::from("09:50"), String::from("10:35")),
+        ]
+    );
+    assert_eq!(
+        merged[0].period_numbers,
+        Some(PeriodNumbers { first: 1, last: 2 })
+    );
+}
+
+#[test]
+fn merge_periods_respects_the_gap() {
+    let periods = || {
+        class_periods(&[
+            (800, 845, "STANDARD", 30, 40),
+            (855, 940, "STANDARD", 30, 40),
+        ])
+    };
+    assert_eq!(merge_periods(periods(), minutes(5)).len(), 2);
+    assert_eq!(merge_periods(periods(), minutes(10)).len(), 1);
+    assert_eq!(merge_periods(periods(), minutes(0)).len(), 2);
+}
+
+#[test]
+fn merge_periods_keeps_changed_halves_apart() {
+    let periods = class_periods(&[
+        (800, 845, "STANDARD", 30, 40),
+        (845, 930, "SUBSTITUTION", 31, 40),
+        (930, 1015, "STANDARD", 31, 41),
+        (1015, 1100, "CANCEL", 31, 41),
+    ]);
+    assert_eq!(merge_periods(periods, minutes(5)).len(), 4);
+}
+
+#[test]
+fn merge_gap_is_configurable() {
+    // The only test using UNTIS_MERGE_GAP, so that tests running in parallel do not interfere
+    std::env::set_var("UNTIS_MERGE_GAP", "10");
+    assert_eq!(merge_gap(), Some(minutes(10)));
+    std::env::set_var("UNTIS_MERGE_GAP", "OFF");
+    assert_eq!(merge_gap(), None);
+    std::env::set_var("UNTIS_MERGE_GAP", "zehn");
+    assert_eq!(merge_gap(), Some(minutes(5)));
+    std::env::remove_var("UNTIS_MERGE_GAP");
+    assert_eq!(merge_gap(), Some(minutes(5)));
+}
+
+#[test]
+fn day_overview_moves_first_and_last_lesson() {
+    let periods = class_periods(&[
+        (800, 845, "CANCEL", 30, 40),
+        (845, 930, "SUBSTITUTION", 31, 40),
+        (950, 1035, "ROOMSUBSTITUTION", 30, 41),
+        (1040, 1125, "ADDITIONAL", 30, 40),
+    ]);
+    let date = periods[0].date;
+    let overview = DayOverview::new(&periods, date);
+    let time = |hour, minute| chrono::NaiveTime::from_hms_opt(hour, minute, 0);
+    assert_eq!(overview.regular_start, time(8, 0));
+    assert_eq!(overview.regular_end, time(10, 35));
+    assert_eq!(overview.start, time(8, 45));
+    assert_eq!(overview.end, time(11, 25));
+    assert_eq!(
+        (
+            overview.cancellations,
+            overview.substitutions,
+            overview.room_changes,
+            overview.additional
+        ),
+        (1, 1, 1, 1)
+    );
+    let lexicon = lexicon(&[]);
+    let spoken = overview.speakable(speech(SpeechFormat::Text, &lexicon), date);
+    assert!(spoken.contains("statt"), "{spoken}");
+}
+
+#[test]
+fn day_overview_without_lessons() {
+    let periods = class_periods(&[(800, 845, "CANCEL", 30, 40), (845, 930, "CANCEL", 30, 40)]);
+    let date = periods[0].date;
+    let overview = DayOverview::new(&periods, date);
+    assert!(overview.regular_start.is_some());
+    assert_eq!((overview.start, overview.end), (None, None));
+    assert_eq!(overview.cancellations, 2);
+
+    let other_day = date.succ_opt().unwrap();
+    let overview = DayOverview::new(&periods, other_day);
+    assert_eq!((overview.regular_start, overview.regular_end), (None, None));
+    let lexicon = lexicon(&[]);
+    assert_eq!(
+        overview.speakable(speech(SpeechFormat::Text, &lexicon), other_day),
+        "Heute ist kein Unterricht."
+    );
+}
+
+#[test]
+fn lexicon_prefers_exact_matches() {
+    let lexicon = lexicon(&[("NW2-H", "Naturwissenschaften"), ("nw2-h", "Enn We")]);
+    assert_eq!(
+        lexicon.lookup("NW2-H").as_deref(),
+        Some("Naturwissenschaften")
+    );
+    assert_eq!(lexicon.lookup("nw2-h").as_deref(), Some("Enn We"));
+    assert!(lexicon.lookup("Nw2-h").is_some());
+    assert_eq!(lexicon.lookup("NW3"), None);
+}
+
+#[test]
+fn lexicon_ignores_case_otherwise() {
+    let lexicon = lexicon(&[("Ev. Religion", "Evangelische Religion")]);
+    assert_eq!(
+        lexicon.lookup("EV. RELIGION").as_deref(),
+        Some("Evangelische Religion")
+    );
+}
+
+#[test]
+fn lexicon_overrides_spoken_names() {
+    let lexicon = lexicon(&[("MÜL", "Müller"), ("Mathematik", "Mathe")]);
+    let text = speech(SpeechFormat::Text, &lexicon);
+    assert_eq!(text.name("MÜL", "MÜL"), "Müller");
+    // Also looked up by the spoken form WebUntis provides
+    assert_eq!(text.name("M", "Mathematik"), "Mathe");
+    assert_eq!(
+        speech(SpeechFormat::Ssml, &lexicon).name("MÜL", "MÜL"),
+        "<sub alias=\"Müller\">MÜL</sub>"
+    );
+}
+
+fn date(year: i32, month: u32, day: u32) -> chrono::NaiveDate {
+    chrono::NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+/// The school year 2025/26 with autumn holidays from 20 to 31 October 2025
+fn school_calendar() -> SchoolCalendar {
+    SchoolCalendar {
+        holidays: vec![Holiday {
+            name: String::from("Herbst"),
+            long_name: String::from("Herbstferien"),
+            start: date(2025, 10, 20),
+            end: date(2025, 10, 31),
+        }],
+        school_years: vec![SchoolYear {
+            id: 1,
+            name: String::from("2025/2026"),
+            start: date(2025, 8, 1),
+            end: date(2026, 7, 31),
+        }],
+        ..SchoolCalendar::default()
+    }
+}
+
+#[test]
+fn next_school_day_skips_weekends_and_holidays() {
+    let calendar = school_calendar();
+    // Thursday to Friday
+    assert_eq!(
+        calendar.next_school_day(date(2025, 10, 9)),
+        Some(date(2025, 10, 10))
+    );
+    // Friday to Monday
+    assert_eq!(
+        calendar.next_school_day(date(2025, 10, 10)),
+        Some(date(2025, 10, 13))
+    );
+    // Friday before the autumn holidays, and during them
+    assert_eq!(
+        calendar.next_school_day(date(2025, 10, 17)),
+        Some(date(2025, 11, 3))
+    );
+    assert_eq!(
+        calendar.next_school_day(date(2025, 10, 25)),
+        Some(date(2025, 11, 3))
+    );
+    assert!(!calendar.is_school_day(date(2025, 10, 22)));
+    assert_eq!(
+        calendar
+            .holiday(date(2025, 10, 31))
+            .map(|h| h.name.as_str()),
+        Some("Herbst")
+    );
+}
+
+#[test]
+fn next_school_day_ends_with_the_school_years() {
+    let calendar = school_calendar();
+    assert_eq!(
+        calendar.next_school_day(date(2026, 7, 30)),
+        Some(date(2026, 7, 31))
+    );
+    assert_eq!(calendar.next_school_day(date(2026, 7, 31)), None);
+    // Without a calendar, only weekends are days without school
+    assert_eq!(
+        SchoolCalendar::default().next_school_day(date(2025, 10, 17)),
+        Some(date(2025, 10, 20))
+    );
+}
+
+fn klasse(id: u64, name: &str) -> TimetableElement {
+    TimetableElement {
+        id,
+        name: name.to_string(),
+        long_name: name.to_string(),
+        displayname: name.to_string(),
+        can_view_timetable: true,
+    }
+}
+
+/// Changes of 5a (three of them) and 10a (two) on 2025-03-17, none on the next day
+fn kiosk_feed() -> KioskFeed {
+    let timetables = vec![
+        (
+            klasse(8, "10a"),
+            class_periods(&[
+                (800, 845, "CANCEL", 30, 40),
+                (845, 930, "STANDARD", 30, 40),
+                (950, 1035, "ROOMSUBSTITUTION", 30, 41),
+            ]),
+        ),
+        (
+            klasse(7, "5a"),
+            class_periods(&[
+                (800, 845, "SUBSTITUTION", 31, 40),
+                (845, 930, "CANCEL", 30, 40),
+                (950, 1035, "ADDITIONAL", 30, 40),
+            ]),
+        ),
+    ];
+    KioskFeed::new(timetables, &[date(2025, 3, 17), date(2025, 3, 18)])
+}
+
+/// The class and state of each entry, by day
+fn kiosk_entries(feed: &KioskFeed) -> Vec<Vec<(String, &'static str)>> {
+    feed.days
+        .iter()
+        .map(|day| {
+            day.classes
+                .iter()
+                .flat_map(|klasse| {
+                    klasse
+                        .entries
+                        .iter()
+                        .map(|entry| (klasse.name.to_string(), entry.state))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn kiosk_feed_lists_changes_by_class() {
+    let feed = kiosk_feed();
+    let classes = feed.days[0]
+        .classes
+        .iter()
+        .map(|klasse| (klasse.name.as_str(), klasse.entries.len()))
+        .collect::<Vec<_>>();
+    assert_eq!(classes, [("5a", 3), ("10a", 2)]);
+    assert!(feed.days[1].classes.is_empty());
+}
+
+#[test]
+fn kiosk_feed_pages_entries() {
+    let feed = kiosk_feed();
+    let first = feed.page(1, 2);
+    assert_eq!((first.page, first.pages), (1, 3));
+    assert_eq!(kiosk_entries(&first)[0].len(), 2);
+    // The next day without changes is kept on every page
+    assert_eq!(first.days.len(), 2);
+
+    let second = feed.page(2, 2);
+    let entries = kiosk_entries(&second);
+    assert_eq!(entries[0][0].0, "5a");
+    assert_eq!(entries[0][1].0, "10a");
+
+    let last = feed.page(3, 2);
+    assert_eq!(kiosk_entries(&last)[0].len(), 1);
+    assert_eq!(feed.page(9, 2).page, 3);
+    assert_eq!(feed.page(0, 2).page, 1);
+}
+
+#[test]
+fn kiosk_feed_fits_on_one_page() {
+    let feed = kiosk_feed();
+    let page = feed.page(1, 100);
+    assert_eq!((page.page, page.pages), (1, 1));
+    assert_eq!(kiosk_entries(&page), kiosk_entries(&feed));
+    assert_eq!(feed.page(1, 0).pages, 5);
+}
+
+/// A single period of class 5a with the given state and teacher and room references
+fn period_with(cell_state: &str, references: Vec<serde_json::Value>) -> Period {
+    let elements = vec![
+        weekly_element(1, 7, "5a"),
+        weekly_element(2, 30, "MÜL"),
+        weekly_element(2, 31, "SCH"),
+        weekly_element(4, 40, "101"),
+        weekly_element(4, 41, "102"),
+    ];
+    let mut references = references;
+    references.insert(0, weekly_reference(1, 7, 0, "REGULAR"));
+    let period = weekly_period(1, 800, 845, cell_state, references);
+    parse_timetable(
+        weekly_timetable(7, elements, vec![period]),
+        7,
+        ParseMode::Strict,
+    )
+    .unwrap()
+    .0
+    .remove(0)
+}
+
+#[test]
+fn occupies_room_ignores_cancelled_and_moved_periods() {
+    let period = period_with("STANDARD", vec![weekly_reference(4, 40, 0, "REGULAR")]);
+    assert!(occupies_room(&period, 40));
+    assert!(!occupies_room(&period, 41));
+
+    let period = period_with("CANCEL", vec![weekly_reference(4, 40, 0, "REGULAR")]);
+    assert!(!occupies_room(&period, 40));
+
+    // Moved from 101 to 102
+    let period = period_with(
+        "ROOMSUBSTITUTION",
+        vec![weekly_reference(4, 41, 40, "SUBSTITUTED")],
+    );
+    assert!(occupies_room(&period, 41));
+    assert!(!occupies_room(&period, 40));
+
+    let period = period_with("STANDARD", vec![weekly_reference(4, 40, 0, "ABSENT")]);
+    assert!(!occupies_room(&period, 40));
+}
+
+#[test]
+fn occupies_teacher_ignores_cancelled_and_substituted_periods() {
+    let period = period_with("STANDARD", vec![weekly_reference(2, 30, 0, "REGULAR")]);
+    assert!(occupies_teacher(&period, 30));
+    assert!(!occupies_teacher(&period, 31));
+
+    let period = period_with("CANCEL", vec![weekly_reference(2, 30, 0, "REGULAR")]);
+    assert!(!occupies_teacher(&period, 30));
+
+    // SCH substitutes for MÜL
+    let period = period_with(
+        "SUBSTITUTION",
+        vec![weekly_reference(2, 31, 30, "SUBSTITUTED")],
+    );
+    assert!(occupies_teacher(&period, 31));
+    assert!(!occupies_teacher(&period, 30));
+
+    let period = period_with("SUBSTITUTION", vec![weekly_reference(2, 30, 0, "ABSENT")]);
+    assert!(!occupies_teacher(&period, 30));
+}
@@ -0,0 +1,314 @@
+use reqwest::header::COOKIE;
+use reqwest::Client;
+use rocket::serde::json::serde_json::json;
+use rocket::serde::{Deserialize, Serialize};
+
+use crate::untis::substitutions::rpc_date;
+use crate::untis::timegrid::{get_timegrid, PeriodNumbers};
+use crate::untis::{
+    build_client, login, logout, rpc_result, untis_date, untis_time, RPCMethods, UserInfo,
+    UsernamePassword,
+};
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct AbsenceExcuseInfo {
+    #[serde(rename = "excuseStatus")]
+    pub(crate) excuse_status: Option<String>,
+    #[serde(rename = "isExcused")]
+    is_excused: bool,
+}
+
+/// An absence in the REST absence list of the class register
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct AbsenceListEntry {
+    pub(crate) id: u64,
+    #[serde(rename = "startDate")]
+    pub(crate) start_date: u64,
+    #[serde(rename = "endDate")]
+    pub(crate) end_date: u64,
+    #[serde(rename = "startTime")]
+    pub(crate) start_time: u64,
+    #[serde(rename = "endTime")]
+    pub(crate) end_time: u64,
+    #[serde(rename = "studentName")]
+    student_name: Option<String>,
+    pub(crate) reason: Option<String>,
+    pub(crate) text: Option<String>,
+    #[serde(rename = "isExcused")]
+    is_excused: bool,
+    pub(crate) excuse: Option<AbsenceExcuseInfo>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct AbsenceListData {
+    pub(crate) absences: Vec<AbsenceListEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct AbsenceListResponse {
+    pub(crate) data: AbsenceListData,
+}
+
+/// A period the student was absent in, as returned by `getTimetableWithAbsences`
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct AbsencePeriodInfo {
+    pub(crate) id: u64,
+    #[serde(alias = "startDate")]
+    pub(crate) date: u64,
+    #[serde(rename = "startTime")]
+    pub(crate) start_time: u64,
+    #[serde(rename = "endTime")]
+    pub(crate) end_time: u64,
+    #[serde(default, alias = "absenceReason")]
+    pub(crate) reason: Option<String>,
+    #[serde(default)]
+    pub(crate) text: Option<String>,
+    #[serde(default)]
+    pub(crate) excused: bool,
+    #[serde(default, rename = "excuseStatus")]
+    pub(crate) excuse_status: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct TimetableWithAbsencesInfo {
+    #[serde(default)]
+    pub(crate) periods: Vec<AbsencePeriodInfo>,
+}
+
+/// Whether an absence has been excused
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub(crate) enum ExcuseStatus {
+    Excused,
+    /// An excuse was handed in, but not accepted
+    Unexcused,
+    /// No excuse has been handed in (yet)
+    Open,
+}
+
+impl ExcuseStatus {
+    pub(crate) fn new(is_excused: bool, status: Option<&str>) -> Self {
+        match (is_excused, status) {
+            (true, _) => ExcuseStatus::Excused,
+            (false, Some(status)) if !status.trim().is_empty() => ExcuseStatus::Unexcused,
+            (false, _) => ExcuseStatus::Open,
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            ExcuseStatus::Excused => "excused",
+            ExcuseStatus::Unexcused => "unexcused",
+            ExcuseStatus::Open => "open",
+        }
+    }
+}
+
+/// A recorded absence of a student, from the start of the first to the end of the last missed
+/// period
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Absence {
+    pub(crate) id: u64,
+    pub(crate) student: Option<String>,
+    #[serde(rename = "startDate")]
+    pub(crate) start_date: chrono::NaiveDate,
+    #[serde(rename = "startTime")]
+    pub(crate) start_time: chrono::NaiveTime,
+    #[serde(rename = "endDate")]
+    pub(crate) end_date: chrono::NaiveDate,
+    #[serde(rename = "endTime")]
+    pub(crate) end_time: chrono::NaiveTime,
+    /// The periods missed, if the absence lies within one day
+    #[serde(rename = "periodNumbers")]
+    pub(crate) period_numbers: Option<PeriodNumbers>,
+    pub(crate) reason: String,
+    pub(crate) text: String,
+    #[serde(rename = "excuseStatus")]
+    pub(crate) excuse_status: ExcuseStatus,
+}
+
+impl TryFrom<AbsenceListEntry> for Absence {
+    type Error = anyhow::Error;
+
+    fn try_from(absence: AbsenceListEntry) -> Result<Self, Self::Error> {
+        let excuse_status = match &absence.excuse {
+            Some(excuse) => ExcuseStatus::new(
+                absence.is_excused || excuse.is_excused,
+                excuse.excuse_status.as_deref(),
+            ),
+            None => ExcuseStatus::new(absence.is_excused, None),
+        };
+        Ok(Absence {
+            id: absence.id,
+            student: absence.student_name,
+            start_date: untis_date(absence.start_date)?,
+            start_time: untis_time(absence.start_time)?,
+            end_date: untis_date(absence.end_date)?,
+            end_time: untis_time(absence.end_time)?,
+            period_numbers: None,
+            reason: absence.reason.unwrap_or_default(),
+            text: absence.text.unwrap_or_default(),
+            excuse_status,
+        })
+    }
+}
+
+impl TryFrom<AbsencePeriodInfo> for Absence {
+    type Error = anyhow::Error;
+
+    fn try_from(period: AbsencePeriodInfo) -> Result<Self, Self::Error> {
+        let date = untis_date(period.date)?;
+        Ok(Absence {
+            id: period.id,
+            student: None,
+            start_date: date,
+            start_time: untis_time(period.start_time)?,
+            end_date: date,
+            end_time: untis_time(period.end_time)?,
+            period_numbers: None,
+            reason: period.reason.unwrap_or_default(),
+            text: period.text.unwrap_or_default(),
+            excuse_status: ExcuseStatus::new(period.excused, period.excuse_status.as_deref()),
+        })
+    }
+}
+
+/// Retrieves the absences of a student from the class register
+async fn get_absence_list(
+    client: &Client,
+    userinfo: &UserInfo,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+) -> anyhow::Result<Vec<AbsenceListEntry>> {
+    debug!("Retrieving absences from {from} to {to}...");
+    let response = client
+        .get(format!(
+            "https://{}/WebUntis/api/classreg/absences/students?studentId={}&startDate={}&endDate={}&excuseStatusId=-1",
+            std::env::var("UNTIS_HOST").expect("'UNTIS_HOST' not defined!"),
+            userinfo.person_id,
+            rpc_date(from),
+            rpc_date(to)
+        ))
+        .header(COOKIE, format!("JSESSIONID={}", userinfo.session_id))
+        .send()
+        .await?
+        .error_for_status()?;
+    let list: AbsenceListResponse = response.json().await?;
+    Ok(list.data.absences)
+}
+
+/// Logs in and retrieves the absences of the logged in student from `from` to `to`
+/// (inclusive). If the absence list is not available, the absent periods of
+/// `getTimetableWithAbsences` are returned instead.
+pub(crate) async fn fetch_absences(
+    user: &UsernamePassword,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+) -> anyhow::Result<Vec<Absence>> {
+    let client = build_client();
+
+    info!("Logging in as {}...", &user.username);
+    let userinfo = login(&client, &user.username, &user.password).await?;
+    let session_id = userinfo.session_id.as_str();
+    let absences = async {
+        info!("Retrieving absences...");
+        let mut absences = match get_absence_list(&client, &userinfo, from, to).await {
+            Ok(list) => list
+                .into_iter()
+                .map(Absence::try_from)
+                .collect::<anyhow::Result<Vec<Absence>>>()?,
+            Err(err) => {
+                info!("Absence list not available ({err}), using getTimetableWithAbsences...");
+                let timetable: TimetableWithAbsencesInfo = rpc_result(
+                    &client,
+                    RPCMethods::GetTimetableWithAbsences,
+                    json!({
+                        "options": {
+                            "startDate": rpc_date(from),
+                            "endDate": rpc_date(to),
+                        }
+                    }),
+                    session_id,
+                    "absences",
+                )
+                .await?;
+                timetable
+                    .periods
+                    .into_iter()
+                    .map(Absence::try_from)
+                    .collect::<anyhow::Result<Vec<Absence>>>()?
+            }
+        };
+        let timegrid = get_timegrid(&client, session_id)
+            .await
+            .inspect_err(|err| warn!("Could not retrieve time grid: {err}"))
+            .ok();
+        for absence in absences
+            .iter_mut()
+            .filter(|absence| absence.start_date == absence.end_date)
+        {
+            absence.period_numbers = timegrid.and_then(|timegrid| {
+                timegrid.numbers(absence.start_date, absence.start_time, absence.end_time)
+            });
+        }
+        absences.sort_by_key(|absence| {
+            chrono::NaiveDateTime::new(absence.start_date, absence.start_time)
+        });
+        anyhow::Ok(absences)
+    }
+    .await;
+    info!("Logging out...");
+    logout(&client, session_id).await?;
+    absences
+}
+
+/// Quotes a CSV field, if it contains separators, quotes or line breaks
+pub(crate) fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// The absences as CSV with a header row, one absence per line
+pub(crate) fn absences_csv(absences: &[Absence]) -> String {
+    let mut csv = String::from(
+        "start_date,start_time,end_date,end_time,first_period,last_period,reason,text,excuse_status\r\n",
+    );
+    for absence in absences {
+        let fields = [
+            absence.start_date.to_string(),
+            absence.start_time.format("%H:%M").to_string(),
+            absence.end_date.to_string(),
+            absence.end_time.format("%H:%M").to_string(),
+            absence
+                .period_numbers
+                .map(|numbers| numbers.first.to_string())
+                .unwrap_or_default(),
+            absence
+                .period_numbers
+                .map(|numbers| numbers.last.to_string())
+                .unwrap_or_default(),
+            absence.reason.to_string(),
+            absence.text.to_string(),
+            absence.excuse_status.name().to_string(),
+        ];
+        csv.push_str(
+            &fields
+                .iter()
+                .map(|field| csv_field(field))
+                .collect::<Vec<String>>()
+                .join(","),
+        );
+        csv.push_str("\r\n");
+    }
+    csv
+}
@@ -0,0 +1,385 @@
+use rocket::serde::json::serde_json;
+use rocket::serde::{Deserialize, Serialize};
+
+use crate::untis::timegrid::{get_timegrid, PeriodNumbers, Timegrid};
+use crate::untis::timetable::{
+    get_timetable, parse_mode, parse_timetable, ElementState, ElementType, OriginalRoom, Period,
+    PeriodState, WeeklyElementHeader,
+};
+use crate::untis::{
+    build_client, login, logout, rpc_result, RPCMethods, TeacherInfo, UsernamePassword,
+};
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct RoomInfo {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    #[serde(rename = "longName")]
+    pub(crate) long_name: String,
+    #[serde(default = "active_default")]
+    pub(crate) active: bool,
+    #[serde(default)]
+    building: String,
+}
+
+pub(crate) fn active_default() -> bool {
+    true
+}
+
+/// A room without lessons in the requested periods
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct FreeRoom {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    #[serde(rename = "longName")]
+    pub(crate) long_name: String,
+    building: String,
+    /// Unknown, if the room had no periods in the whole week
+    pub(crate) capacity: Option<u64>,
+    /// Whether the room is only free, because its period was cancelled or moved to another room
+    freed: bool,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct FreeRooms {
+    pub(crate) date: chrono::NaiveDate,
+    #[serde(rename = "startTime")]
+    pub(crate) start_time: chrono::NaiveTime,
+    #[serde(rename = "endTime")]
+    pub(crate) end_time: chrono::NaiveTime,
+    #[serde(rename = "periodNumbers")]
+    pub(crate) period_numbers: PeriodNumbers,
+    pub(crate) rooms: Vec<FreeRoom>,
+    /// Rooms whose timetable could not be read, they may or may not be free
+    pub(crate) skipped: Vec<String>,
+}
+
+/// None of the timetables to look through could be read, usually because the account may not
+/// view them
+#[derive(Debug)]
+pub(crate) struct TimetablesUnreadable(pub(crate) &'static str);
+
+impl std::fmt::Display for TimetablesUnreadable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Could not read the timetable of any {}", self.0)
+    }
+}
+
+impl std::error::Error for TimetablesUnreadable {}
+
+/// Parses a period number (`3`) or range of period numbers (`3-4`)
+pub(crate) fn parse_period_numbers(numbers: &str) -> Option<PeriodNumbers> {
+    let (first, last) = numbers.split_once('-').unwrap_or((numbers, numbers));
+    let numbers = PeriodNumbers {
+        first: first.trim().parse().ok()?,
+        last: last.trim().parse().ok()?,
+    };
+    (numbers.first <= numbers.last).then_some(numbers)
+}
+
+/// Whether `period` keeps the room with `room_id` occupied. Cancelled periods, periods moved to
+/// another room and periods the room was removed from do not.
+pub(crate) fn occupies_room(period: &Period, room_id: u64) -> bool {
+    period.state != PeriodState::Cancel
+        && period
+            .rooms
+            .iter()
+            .any(|room| room.id == room_id && room.state != ElementState::Absent)
+}
+
+/// The periods of a room's weekly timetable and the capacity of the room, if it is listed
+fn parse_room_timetable(
+    timetable: serde_json::Value,
+    room_id: u64,
+) -> anyhow::Result<(Vec<Period>, Option<u64>)> {
+    let capacity = timetable
+        .pointer("/data/result/data/elements")
+        .and_then(|elements| elements.as_array())
+        .into_iter()
+        .flatten()
+        .filter(|element| {
+            WeeklyElementHeader::deserialize(*element)
+                .is_ok_and(|header| header.element_type == 4 && header.id == room_id)
+        })
+        .find_map(|element| OriginalRoom::deserialize(element).ok())
+        .map(|room| room.room_capacity);
+    Ok((parse_element_timetable(timetable, room_id)?, capacity))
+}
+
+/// The periods of a weekly timetable of a class, room or teacher. Elements without any periods
+/// in the week have no timetable at all.
+pub(crate) fn parse_element_timetable(
+    timetable: serde_json::Value,
+    element_id: u64,
+) -> anyhow::Result<Vec<Period>> {
+    if timetable
+        .pointer(&format!("/data/result/data/elementPeriods/{element_id}"))
+        .is_none()
+    {
+        return Ok(vec![]);
+    }
+    let (periods, _) = parse_timetable(timetable, element_id, parse_mode())?;
+    Ok(periods)
+}
+
+/// Logs in and looks for rooms that are free in the periods `numbers` on `date`
+pub(crate) async fn find_free_rooms(
+    user: &UsernamePassword,
+    date: chrono::NaiveDate,
+    numbers: PeriodNumbers,
+    min_capacity: Option<u64>,
+) -> anyhow::Result<Option<FreeRooms>> {
+    let client = build_client();
+
+    info!("Logging in as {}...", &user.username);
+    let userinfo = login(&client, &user.username, &user.password).await?;
+    let session_id = userinfo.session_id.as_str();
+    let free_rooms = async {
+        let timegrid = get_timegrid(&client, session_id).await?;
+        let Some((start_time, end_time)) = timegrid.span(date, numbers) else {
+            return Ok(None);
+        };
+        let rooms: Vec<RoomInfo> = rpc_result(
+            &client,
+            RPCMethods::GetRooms,
+            serde_json::Value::Null,
+            session_id,
+            "rooms",
+        )
+        .await?;
+
+        let rooms = rooms
+            .into_iter()
+            .filter(|room| room.active)
+            .collect::<Vec<RoomInfo>>();
+        info!("Retrieving timetables of {} rooms...", rooms.len());
+        let total = rooms.len();
+        let mut free_rooms = vec![];
+        let mut skipped = vec![];
+        for room in rooms {
+            let timetable =
+                get_timetable(&client, session_id, ElementType::Room, room.id, date).await;
+            let (periods, capacity) = match timetable
+                .map_err(anyhow::Error::from)
+                .and_then(|timetable| parse_room_timetable(timetable, room.id))
+            {
+                Ok(timetable) => timetable,
+                Err(err) => {
+                    warn!("Skipping room {}: {err}", room.name);
+                    skipped.push(room.name);
+                    continue;
+                }
+            };
+            if min_capacity.is_some_and(|min| capacity.is_none_or(|capacity| capacity < min)) {
+                continue;
+            }
+            let overlapping = periods
+                .iter()
+                .filter(|period| {
+                    period.date == date
+                        && period.start_time < end_time
+                        && start_time < period.end_time
+                })
+                .collect::<Vec<&Period>>();
+            if overlapping
+                .iter()
+                .any(|period| occupies_room(period, room.id))
+            {
+                continue;
+            }
+            free_rooms.push(FreeRoom {
+                id: room.id,
+                name: room.name,
+                long_name: room.long_name,
+                building: room.building,
+                capacity,
+                freed: !overlapping.is_empty(),
+            });
+        }
+        if total > 0 && skipped.len() == total {
+            return Err(TimetablesUnreadable("room").into());
+        }
+        free_rooms
+            .sort_by_cached_key(|room| (room.building.to_lowercase(), room.name.to_lowercase()));
+        anyhow::Ok(Some(FreeRooms {
+            date,
+            start_time,
+            end_time,
+            period_numbers: numbers,
+            rooms: free_rooms,
+            skipped,
+        }))
+    }
+    .await;
+    info!("Logging out...");
+    logout(&client, session_id).await?;
+    free_rooms
+}
+
+/// A teacher without lessons in the requested time
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct FreeTeacher {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    #[serde(rename = "foreName")]
+    pub(crate) fore_name: String,
+    #[serde(rename = "longName")]
+    pub(crate) long_name: String,
+    /// Whether the teacher is only free, because their lesson was cancelled or taken over
+    freed: bool,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct FreeTeachers {
+    pub(crate) date: chrono::NaiveDate,
+    #[serde(rename = "startTime")]
+    pub(crate) start_time: chrono::NaiveTime,
+    #[serde(rename = "endTime")]
+    pub(crate) end_time: chrono::NaiveTime,
+    #[serde(rename = "periodNumbers")]
+    pub(crate) period_numbers: Option<PeriodNumbers>,
+    pub(crate) teachers: Vec<FreeTeacher>,
+    /// Teachers whose timetable could not be read, they may or may not be free
+    pub(crate) skipped: Vec<String>,
+}
+
+/// The time free teachers are looked for: periods of the time grid or any time range
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum RequestedTime {
+    Periods(PeriodNumbers),
+    Range(chrono::NaiveTime, chrono::NaiveTime),
+}
+
+impl RequestedTime {
+    /// Start, end and the periods of the requested time on `date`. `None`, if the requested
+    /// periods do not exist on that day.
+    pub(crate) fn resolve(
+        self,
+        timegrid: Option<&Timegrid>,
+        date: chrono::NaiveDate,
+    ) -> Option<(chrono::NaiveTime, chrono::NaiveTime, Option<PeriodNumbers>)> {
+        match self {
+            RequestedTime::Periods(numbers) => {
+                let (start, end) = timegrid?.span(date, numbers)?;
+                Some((start, end, Some(numbers)))
+            }
+            RequestedTime::Range(start, end) => Some((
+                start,
+                end,
+                timegrid.and_then(|timegrid| timegrid.numbers(date, start, end)),
+            )),
+        }
+    }
+}
+
+/// Whether `period` keeps the teacher with `teacher_id` busy. Teachers substituting in a period
+/// are busy, teachers of cancelled periods and teachers who were substituted or are absent are
+/// not.
+pub(crate) fn occupies_teacher(period: &Period, teacher_id: u64) -> bool {
+    period.state != PeriodState::Cancel
+        && period
+            .teachers
+            .iter()
+            .any(|teacher| teacher.id == teacher_id && teacher.state != ElementState::Absent)
+}
+
+/// Logs in and looks for teachers without lessons at the requested time on `date`
+pub(crate) async fn find_free_teachers(
+    user: &UsernamePassword,
+    date: chrono::NaiveDate,
+    time: RequestedTime,
+) -> anyhow::Result<Option<FreeTeachers>> {
+    let client = build_client();
+
+    info!("Logging in as {}...", &user.username);
+    let userinfo = login(&client, &user.username, &user.password).await?;
+    let session_id = userinfo.session_id.as_str();
+    let free_teachers = async {
+        let timegrid = get_timegrid(&client, session_id)
+            .await
+            .inspect_err(|err| warn!("Could not retrieve time grid: {err}"))
+            .ok();
+        let Some((start_time, end_time, period_numbers)) = time.resolve(timegrid, date) else {
+            return Ok(None);
+        };
+        let teachers: Vec<TeacherInfo> = rpc_result(
+            &client,
+            RPCMethods::GetTeachers,
+            serde_json::Value::Null,
+            session_id,
+            "teachers",
+        )
+        .await?;
+
+        let teachers = teachers
+            .into_iter()
+            .filter(|teacher| teacher.active)
+            .collect::<Vec<TeacherInfo>>();
+        info!("Retrieving timetables of {} teachers...", teachers.len());
+        let total = teachers.len();
+        let mut free_teachers = vec![];
+        let mut skipped = vec![];
+        for teacher in teachers {
+            let timetable =
+                get_timetable(&client, session_id, ElementType::Teacher, teacher.id, date).await;
+            let periods = match timetable
+                .map_err(anyhow::Error::from)
+                .and_then(|timetable| parse_element_timetable(timetable, teacher.id))
+            {
+                Ok(periods) => periods,
+                Err(err) => {
+                    warn!("Skipping teacher {}: {err}", teacher.name);
+                    skipped.push(teacher.name);
+                    continue;
+                }
+            };
+            let overlapping = periods
+                .iter()
+                .filter(|period| {
+                    period.date == date
+                        && period.start_time < end_time
+                        && start_time < period.end_time
+                })
+                .collect::<Vec<&Period>>();
+            if overlapping
+                .iter()
+                .any(|period| occupies_teacher(period, teacher.id))
+            {
+                continue;
+            }
+            free_teachers.push(FreeTeacher {
+                id: teacher.id,
+                name: teacher.name,
+                fore_name: teacher.fore_name,
+                long_name: teacher.long_name,
+                freed: !overlapping.is_empty(),
+            });
+        }
+        if total > 0 && skipped.len() == total {
+            return Err(TimetablesUnreadable("teacher").into());
+        }
+        free_teachers.sort_by_cached_key(|teacher| {
+            (
+                teacher.long_name.to_lowercase(),
+                teacher.name.to_lowercase(),
+            )
+        });
+        anyhow::Ok(Some(FreeTeachers {
+            date,
+            start_time,
+            end_time,
+            period_numbers,
+            teachers: free_teachers,
+            skipped,
+        }))
+    }
+    .await;
+    info!("Logging out...");
+    logout(&client, session_id).await?;
+    free_teachers
+}
@@ -0,0 +1,195 @@
+use chrono::Datelike;
+use reqwest::Client;
+use rocket::serde::json::serde_json;
+use rocket::serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+
+use crate::untis::{
+    build_client, login, logout, rpc_result, untis_date, RPCMethods, UsernamePassword,
+};
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct HolidayInfo {
+    pub(crate) name: String,
+    #[serde(rename = "longName")]
+    pub(crate) long_name: String,
+    #[serde(rename = "startDate")]
+    pub(crate) start_date: u64,
+    #[serde(rename = "endDate")]
+    pub(crate) end_date: u64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct SchoolYearInfo {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    #[serde(rename = "startDate")]
+    pub(crate) start_date: u64,
+    #[serde(rename = "endDate")]
+    pub(crate) end_date: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Holiday {
+    pub(crate) name: String,
+    #[serde(rename = "longName")]
+    pub(crate) long_name: String,
+    pub(crate) start: chrono::NaiveDate,
+    /// Last day of the holiday (inclusive)
+    pub(crate) end: chrono::NaiveDate,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct SchoolYear {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    pub(crate) start: chrono::NaiveDate,
+    pub(crate) end: chrono::NaiveDate,
+}
+
+impl TryFrom<SchoolYearInfo> for SchoolYear {
+    type Error = anyhow::Error;
+
+    fn try_from(info: SchoolYearInfo) -> anyhow::Result<Self> {
+        Ok(SchoolYear {
+            id: info.id,
+            name: info.name,
+            start: untis_date(info.start_date)?,
+            end: untis_date(info.end_date)?,
+        })
+    }
+}
+
+/// Holidays and school years of the school. Without them (e.g. if they could not be retrieved),
+/// only weekends are days without school.
+#[derive(Serialize, Debug, Default, Clone)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct SchoolCalendar {
+    pub(crate) holidays: Vec<Holiday>,
+    #[serde(rename = "schoolYears")]
+    pub(crate) school_years: Vec<SchoolYear>,
+    #[serde(rename = "currentSchoolYear")]
+    pub(crate) current_school_year: Option<SchoolYear>,
+    /// When the calendar was retrieved, it is retrieved again the next day
+    #[serde(skip)]
+    pub(crate) retrieved: Option<chrono::NaiveDate>,
+}
+
+impl SchoolCalendar {
+    pub(crate) fn holiday(&self, date: chrono::NaiveDate) -> Option<&Holiday> {
+        self.holidays
+            .iter()
+            .find(|holiday| holiday.start <= date && date <= holiday.end)
+    }
+
+    /// Weekdays, that are neither holidays nor outside of all school years
+    pub(crate) fn is_school_day(&self, date: chrono::NaiveDate) -> bool {
+        date.weekday().num_days_from_monday() < 5
+            && self.holiday(date).is_none()
+            && (self.school_years.is_empty()
+                || self
+                    .school_years
+                    .iter()
+                    .any(|year| year.start <= date && date <= year.end))
+    }
+
+    /// The first school day after `date`, if there is one within a year
+    pub(crate) fn next_school_day(&self, date: chrono::NaiveDate) -> Option<chrono::NaiveDate> {
+        (1..=366)
+            .map(|days| date + chrono::Days::new(days))
+            .find(|date| self.is_school_day(*date))
+    }
+}
+
+/// Retrieves the holidays and school years of the school
+async fn get_school_calendar(client: &Client, jsession_id: &str) -> anyhow::Result<SchoolCalendar> {
+    debug!("Retrieving holidays and school years...");
+    let holidays: Vec<HolidayInfo> = rpc_result(
+        client,
+        RPCMethods::GetHolidays,
+        serde_json::Value::Null,
+        jsession_id,
+        "holidays",
+    )
+    .await?;
+    let school_years: Vec<SchoolYearInfo> = rpc_result(
+        client,
+        RPCMethods::GetSchoolyears,
+        serde_json::Value::Null,
+        jsession_id,
+        "school years",
+    )
+    .await?;
+    // There is no current school year during the summer holidays
+    let current_school_year: Option<SchoolYearInfo> = rpc_result(
+        client,
+        RPCMethods::GetCurrentSchoolyear,
+        serde_json::Value::Null,
+        jsession_id,
+        "current school year",
+    )
+    .await
+    .inspect_err(|err| info!("No current school year: {err}"))
+    .ok();
+
+    Ok(SchoolCalendar {
+        holidays: holidays
+            .into_iter()
+            .map(|holiday| {
+                Ok(Holiday {
+                    name: holiday.name,
+                    long_name: holiday.long_name,
+                    start: untis_date(holiday.start_date)?,
+                    end: untis_date(holiday.end_date)?,
+                })
+            })
+            .collect::<anyhow::Result<Vec<Holiday>>>()?,
+        school_years: school_years
+            .into_iter()
+            .map(SchoolYear::try_from)
+            .collect::<anyhow::Result<Vec<SchoolYear>>>()?,
+        current_school_year: current_school_year.map(SchoolYear::try_from).transpose()?,
+        retrieved: Some(chrono::Local::now().date_naive()),
+    })
+}
+
+/// Returns the school calendar, retrieving it at most once a day. If that fails, the last retrieved
+/// calendar (or an empty one, so that only weekends are considered days without school) is used for
+/// this request only. Failures are not cached, as they might be caused by the credentials of a
+/// single account, and would otherwise disable holidays for every account for the rest of the day.
+pub(crate) async fn school_calendar(user: &UsernamePassword) -> Arc<SchoolCalendar> {
+    static CALENDAR: RwLock<Option<Arc<SchoolCalendar>>> = RwLock::new(None);
+    let today = chrono::Local::now().date_naive();
+    if let Some(calendar) = CALENDAR
+        .read()
+        .unwrap()
+        .as_ref()
+        .filter(|calendar| calendar.retrieved == Some(today))
+    {
+        return calendar.clone();
+    }
+
+    let client = build_client();
+    let calendar = async {
+        let userinfo = login(&client, &user.username, &user.password).await?;
+        let calendar = get_school_calendar(&client, &userinfo.session_id).await;
+        logout(&client, &userinfo.session_id).await?;
+        calendar
+    }
+    .await;
+    match calendar {
+        Ok(calendar) => {
+            let calendar = Arc::new(calendar);
+            *CALENDAR.write().unwrap() = Some(calendar.clone());
+            calendar
+        }
+        Err(err) => {
+            warn!("Could not retrieve school calendar: {err:#}");
+            CALENDAR.read().unwrap().clone().unwrap_or_default()
+        }
+    }
+}
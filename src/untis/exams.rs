@@ -0,0 +1,409 @@
+use reqwest::header::COOKIE;
+use reqwest::Client;
+use rocket::serde::json::serde_json::{self, json};
+use rocket::serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::speech::{speakable_day_name, Speech};
+use crate::untis::substitutions::rpc_date;
+use crate::untis::timegrid::{get_timegrid, PeriodNumbers};
+use crate::untis::timetable::{get_elements, ElementType, TimetableElement};
+use crate::untis::{
+    build_client, login, logout, rpc_result, untis_date, untis_time, RPCMethods, UserInfo,
+    UsernamePassword,
+};
+
+/// An exam in the REST exam list, referencing elements by name
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct ExamListEntry {
+    pub(crate) id: u64,
+    #[serde(rename = "examType")]
+    exam_type: Option<String>,
+    pub(crate) name: Option<String>,
+    #[serde(default, rename = "studentClass")]
+    pub(crate) klassen: Vec<String>,
+    #[serde(rename = "examDate")]
+    pub(crate) date: u64,
+    #[serde(rename = "startTime")]
+    pub(crate) start_time: u64,
+    #[serde(rename = "endTime")]
+    pub(crate) end_time: u64,
+    pub(crate) subject: Option<String>,
+    #[serde(default)]
+    pub(crate) teachers: Vec<String>,
+    #[serde(default)]
+    pub(crate) rooms: Vec<String>,
+    pub(crate) text: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct ExamListData {
+    pub(crate) exams: Vec<ExamListEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct ExamListResponse {
+    pub(crate) data: ExamListData,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct ExamTypeInfo {
+    pub(crate) id: u64,
+    #[serde(rename = "longName")]
+    pub(crate) long_name: String,
+}
+
+/// An exam as returned by `getExams`, referencing elements by id
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct ExamInfo {
+    pub(crate) id: u64,
+    #[serde(default)]
+    pub(crate) classes: Vec<u64>,
+    #[serde(default)]
+    pub(crate) teachers: Vec<u64>,
+    pub(crate) subject: Option<u64>,
+    pub(crate) date: u64,
+    #[serde(rename = "startTime")]
+    pub(crate) start_time: u64,
+    #[serde(rename = "endTime")]
+    pub(crate) end_time: u64,
+}
+
+/// An upcoming exam, with its subject and rooms linked to the elements of the timetable
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Exam {
+    pub(crate) id: u64,
+    /// e.g. "Klausur" or "Test"
+    #[serde(rename = "examType")]
+    exam_type: String,
+    pub(crate) name: String,
+    pub(crate) date: chrono::NaiveDate,
+    #[serde(rename = "startTime")]
+    pub(crate) start_time: chrono::NaiveTime,
+    #[serde(rename = "endTime")]
+    pub(crate) end_time: chrono::NaiveTime,
+    #[serde(rename = "periodNumbers")]
+    pub(crate) period_numbers: Option<PeriodNumbers>,
+    pub(crate) klassen: Vec<String>,
+    pub(crate) subject: Option<TimetableElement>,
+    pub(crate) teachers: Vec<String>,
+    pub(crate) rooms: Vec<TimetableElement>,
+    pub(crate) text: String,
+}
+
+/// Looks up an element by id or name. Elements, the user may not see, are kept with the
+/// information at hand.
+pub(crate) fn linked_element(
+    elements: &[TimetableElement],
+    id: Option<u64>,
+    name: &str,
+) -> TimetableElement {
+    elements
+        .iter()
+        .find(|element| match id {
+            Some(id) => element.id == id,
+            None => {
+                element.name.eq_ignore_ascii_case(name)
+                    || element.long_name.eq_ignore_ascii_case(name)
+            }
+        })
+        .cloned()
+        .unwrap_or_else(|| TimetableElement {
+            id: id.unwrap_or(0),
+            name: name.to_string(),
+            long_name: name.to_string(),
+            displayname: name.to_string(),
+            can_view_timetable: false,
+        })
+}
+
+/// Retrieves the exams of the logged in student from the REST exam list
+async fn get_exam_list(
+    client: &Client,
+    userinfo: &UserInfo,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+) -> anyhow::Result<Vec<ExamListEntry>> {
+    debug!("Retrieving exam list from {from} to {to}...");
+    let response = client
+        .get(format!(
+            "https://{}/WebUntis/api/exams?studentId={}&klasseId={}&startDate={}&endDate={}",
+            std::env::var("UNTIS_HOST").expect("'UNTIS_HOST' not defined!"),
+            userinfo.person_id,
+            userinfo.klasse_id,
+            rpc_date(from),
+            rpc_date(to)
+        ))
+        .header(COOKIE, format!("JSESSIONID={}", userinfo.session_id))
+        .send()
+        .await?
+        .error_for_status()?;
+    let list: ExamListResponse = response.json().await?;
+    Ok(list.data.exams)
+}
+
+/// Retrieves the exams of all exam types with `getExams`, resolving the referenced elements with
+/// the page config
+async fn get_exams(
+    client: &Client,
+    jsession_id: &str,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+    subjects: &[TimetableElement],
+) -> anyhow::Result<Vec<Exam>> {
+    let exam_types: Vec<ExamTypeInfo> = rpc_result(
+        client,
+        RPCMethods::GetExamTypes,
+        serde_json::Value::Null,
+        jsession_id,
+        "exam types",
+    )
+    .await?;
+    let names = |element_type: ElementType| async move {
+        get_elements(client, jsession_id, element_type)
+            .await
+            .inspect_err(|err| info!("Could not retrieve {element_type:?} names: {err}"))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|element| (element.id, element.name))
+            .collect::<HashMap<u64, String>>()
+    };
+    let klassen = names(ElementType::Klasse).await;
+    let teachers = names(ElementType::Teacher).await;
+    let name = |names: &HashMap<u64, String>, id: u64| {
+        names.get(&id).cloned().unwrap_or_else(|| id.to_string())
+    };
+
+    let mut exams = vec![];
+    for exam_type in exam_types {
+        debug!("Retrieving exams of type {}...", exam_type.long_name);
+        let infos: Vec<ExamInfo> = rpc_result(
+            client,
+            RPCMethods::GetExams,
+            json!({
+                "examTypeId": exam_type.id,
+                "startDate": rpc_date(from),
+                "endDate": rpc_date(to),
+            }),
+            jsession_id,
+            "exams",
+        )
+        .await?;
+        for info in infos {
+            exams.push(Exam {
+                id: info.id,
+                exam_type: exam_type.long_name.to_string(),
+                name: String::new(),
+                date: untis_date(info.date)?,
+                start_time: untis_time(info.start_time)?,
+                end_time: untis_time(info.end_time)?,
+                period_numbers: None,
+                klassen: info.classes.iter().map(|id| name(&klassen, *id)).collect(),
+                subject: info
+                    .subject
+                    .map(|id| linked_element(subjects, Some(id), &id.to_string())),
+                teachers: info
+                    .teachers
+                    .iter()
+                    .map(|id| name(&teachers, *id))
+                    .collect(),
+                rooms: vec![],
+                text: String::new(),
+            });
+        }
+    }
+    Ok(exams)
+}
+
+/// Logs in and retrieves the exams from `from` to `to` (inclusive). The REST exam list is only
+/// available to students, otherwise `getExams` is used.
+pub(crate) async fn fetch_exams(
+    user: &UsernamePassword,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+) -> anyhow::Result<Vec<Exam>> {
+    let client = build_client();
+
+    info!("Logging in as {}...", &user.username);
+    let userinfo = login(&client, &user.username, &user.password).await?;
+    let session_id = userinfo.session_id.as_str();
+    let exams = async {
+        let elements = |element_type: ElementType| {
+            let client = &client;
+            async move {
+                get_elements(client, session_id, element_type)
+                    .await
+                    .inspect_err(|err| info!("Could not retrieve {element_type:?} list: {err}"))
+                    .unwrap_or_default()
+            }
+        };
+        let subjects = elements(ElementType::Subject).await;
+        let rooms = elements(ElementType::Room).await;
+        let timegrid = get_timegrid(&client, session_id)
+            .await
+            .inspect_err(|err| warn!("Could not retrieve time grid: {err}"))
+            .ok();
+
+        info!("Retrieving exams...");
+        let mut exams = match get_exam_list(&client, &userinfo, from, to).await {
+            Ok(list) => list
+                .into_iter()
+                .map(|exam| {
+                    Ok(Exam {
+                        id: exam.id,
+                        exam_type: exam.exam_type.unwrap_or_default(),
+                        name: exam.name.unwrap_or_default(),
+                        date: untis_date(exam.date)?,
+                        start_time: untis_time(exam.start_time)?,
+                        end_time: untis_time(exam.end_time)?,
+                        period_numbers: None,
+                        klassen: exam.klassen,
+                        subject: exam
+                            .subject
+                            .filter(|subject| !subject.is_empty())
+                            .map(|subject| linked_element(&subjects, None, &subject)),
+                        teachers: exam.teachers,
+                        rooms: exam
+                            .rooms
+                            .iter()
+                            .map(|room| linked_element(&rooms, None, room))
+                            .collect(),
+                        text: exam.text.unwrap_or_default(),
+                    })
+                })
+                .collect::<anyhow::Result<Vec<Exam>>>()?,
+            Err(err) => {
+                info!("Exam list not available ({err}), using getExams...");
+                get_exams(&client, session_id, from, to, &subjects).await?
+            }
+        };
+        for exam in exams.iter_mut() {
+            exam.period_numbers = timegrid
+                .and_then(|timegrid| timegrid.numbers(exam.date, exam.start_time, exam.end_time));
+        }
+        exams.sort_by_key(|exam| chrono::NaiveDateTime::new(exam.date, exam.start_time));
+        anyhow::Ok(exams)
+    }
+    .await;
+    info!("Logging out...");
+    logout(&client, session_id).await?;
+    exams
+}
+
+impl Exam {
+    /// "Am Donnerstag Mathe-Klausur in der 3. Stunde."
+    pub(crate) fn speakable(&self, speech: Speech, today: chrono::NaiveDate) -> String {
+        let day = speakable_day_name(speech, self.date, today);
+        let subject = match &self.subject {
+            Some(subject) => speech.name(&subject.name, &subject.long_name),
+            None => speech.text(&self.name),
+        };
+        let time = speech.time_span(self.start_time, self.end_time, self.period_numbers);
+        if self.exam_type.is_empty() {
+            speech.translate(
+                "exam-untyped",
+                &[("day", &day), ("subject", &subject), ("time", &time)],
+            )
+        } else {
+            speech.translate(
+                "exam",
+                &[
+                    ("day", &day),
+                    ("subject", &subject),
+                    ("type", &speech.name(&self.exam_type, &self.exam_type)),
+                    ("time", &time),
+                ],
+            )
+        }
+    }
+}
+
+/// Escapes text for iCalendar property values
+pub(crate) fn escape_ics(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Folds a content line after 75 octets, as iCalendar requires
+pub(crate) fn fold_ics_line(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for char in line.chars() {
+        if length + char.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(char);
+        length += char.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// The exams as iCalendar, with times in the local time of the school
+pub(crate) fn exams_ics(exams: &[Exam]) -> String {
+    let mut lines = vec![
+        String::from("BEGIN:VCALENDAR"),
+        String::from("VERSION:2.0"),
+        format!("PRODID:-//{}//exams//EN", env!("CARGO_PKG_NAME")),
+        String::from("CALSCALE:GREGORIAN"),
+    ];
+    let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    for exam in exams {
+        let subject = exam
+            .subject
+            .as_ref()
+            .map(|subject| subject.long_name.as_str())
+            .unwrap_or(&exam.name);
+        let summary = [subject, &exam.exam_type]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<&str>>()
+            .join(" – ");
+        lines.push(String::from("BEGIN:VEVENT"));
+        lines.push(format!(
+            "UID:exam-{}@{}",
+            exam.id,
+            std::env::var("UNTIS_HOST").unwrap_or_default()
+        ));
+        lines.push(format!("DTSTAMP:{stamp}"));
+        lines.push(format!(
+            "DTSTART:{}",
+            chrono::NaiveDateTime::new(exam.date, exam.start_time).format("%Y%m%dT%H%M%S")
+        ));
+        lines.push(format!(
+            "DTEND:{}",
+            chrono::NaiveDateTime::new(exam.date, exam.end_time).format("%Y%m%dT%H%M%S")
+        ));
+        lines.push(format!("SUMMARY:{}", escape_ics(&summary)));
+        if !exam.rooms.is_empty() {
+            let rooms = exam
+                .rooms
+                .iter()
+                .map(|room| room.long_name.as_str())
+                .collect::<Vec<&str>>()
+                .join(", ");
+            lines.push(format!("LOCATION:{}", escape_ics(&rooms)));
+        }
+        let description = [exam.name.as_str(), exam.text.as_str()]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<&str>>()
+            .join("\n");
+        if !description.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape_ics(&description)));
+        }
+        lines.push(String::from("END:VEVENT"));
+    }
+    lines.push(String::from("END:VCALENDAR"));
+    lines.iter().map(|line| fold_ics_line(line)).collect()
+}
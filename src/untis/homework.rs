@@ -0,0 +1,227 @@
+use chrono::Datelike;
+use reqwest::header::COOKIE;
+use reqwest::Client;
+use rocket::serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::assistant::weekday_message;
+use crate::speech::Speech;
+use crate::untis::exams::linked_element;
+use crate::untis::substitutions::rpc_date;
+use crate::untis::timetable::{get_elements, ElementType, TimetableElement};
+use crate::untis::{build_client, login, logout, untis_date, UsernamePassword};
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct HomeworkInfo {
+    pub(crate) id: u64,
+    #[serde(rename = "lessonId")]
+    pub(crate) lesson_id: u64,
+    pub(crate) date: u64,
+    #[serde(rename = "dueDate")]
+    due_date: u64,
+    #[serde(default)]
+    pub(crate) text: String,
+    #[serde(default)]
+    remark: String,
+    pub(crate) completed: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct HomeworkRecord {
+    #[serde(rename = "homeworkId")]
+    homework_id: u64,
+    #[serde(rename = "teacherId")]
+    pub(crate) teacher_id: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct HomeworkLesson {
+    pub(crate) id: u64,
+    pub(crate) subject: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct HomeworkTeacher {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct HomeworkData {
+    #[serde(default)]
+    records: Vec<HomeworkRecord>,
+    #[serde(default)]
+    homeworks: Vec<HomeworkInfo>,
+    #[serde(default)]
+    pub(crate) teachers: Vec<HomeworkTeacher>,
+    #[serde(default)]
+    pub(crate) lessons: Vec<HomeworkLesson>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct HomeworkResponse {
+    pub(crate) data: HomeworkData,
+}
+
+/// Homework given in a lesson, with its subject linked to the elements of the timetable
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Homework {
+    pub(crate) id: u64,
+    pub(crate) subject: Option<TimetableElement>,
+    pub(crate) teacher: Option<String>,
+    /// When the homework was given
+    pub(crate) date: chrono::NaiveDate,
+    #[serde(rename = "dueDate")]
+    due_date: chrono::NaiveDate,
+    pub(crate) text: String,
+    remark: String,
+    pub(crate) completed: bool,
+}
+
+impl Homework {
+    /// The due date as "morgen", "Montag" or "Montag, den 20. Oktober"
+    pub(crate) fn speakable_due(
+        speech: Speech,
+        due_date: chrono::NaiveDate,
+        today: chrono::NaiveDate,
+    ) -> String {
+        let weekday = speech.translate(weekday_message(due_date.weekday()), &[]);
+        if due_date == today + chrono::Days::new(1) {
+            speech.translate("homework-due-tomorrow", &[])
+        } else if due_date > today && due_date <= today + chrono::Days::new(6) {
+            speech.translate("homework-due-weekday", &[("weekday", &weekday)])
+        } else {
+            speech.translate(
+                "homework-due-date",
+                &[("weekday", &weekday), ("date", &speech.date(due_date))],
+            )
+        }
+    }
+
+    /// "Für morgen: Englisch Hausaufgabe 'Seite 42, Nummer 3'."
+    pub(crate) fn speakable(&self, speech: Speech, today: chrono::NaiveDate) -> String {
+        let due = Homework::speakable_due(speech, self.due_date, today);
+        let subject = match &self.subject {
+            Some(subject) => speech.name(&subject.name, &subject.long_name),
+            None => speech.translate("homework-no-subject", &[]),
+        };
+        speech.translate(
+            "homework",
+            &[
+                ("due", &due),
+                ("subject", &subject),
+                ("text", &speech.text(self.text.trim())),
+            ],
+        )
+    }
+}
+
+/// How long before its due date homework may have been given
+const HOMEWORK_LOOKBACK_DAYS: u64 = 28;
+
+/// Retrieves the homework of the lessons from `from` to `to`
+async fn get_homework(
+    client: &Client,
+    jsession_id: &str,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+) -> anyhow::Result<HomeworkData> {
+    debug!("Retrieving homework from {from} to {to}...");
+    let response = client
+        .get(format!(
+            "https://{}/WebUntis/api/homeworks/lessons?startDate={}&endDate={}",
+            std::env::var("UNTIS_HOST").expect("'UNTIS_HOST' not defined!"),
+            rpc_date(from),
+            rpc_date(to)
+        ))
+        .header(COOKIE, format!("JSESSIONID={jsession_id}"))
+        .send()
+        .await?
+        .error_for_status()?;
+    let homework: HomeworkResponse = response.json().await?;
+    Ok(homework.data)
+}
+
+/// A requested date range reaches beyond the dates that can be represented
+#[derive(Debug)]
+pub(crate) struct DateOutOfRange;
+
+impl std::fmt::Display for DateOutOfRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The date range reaches beyond the dates that can be represented"
+        )
+    }
+}
+
+impl std::error::Error for DateOutOfRange {}
+
+/// Logs in and retrieves the homework due from `from` to `to` (inclusive)
+pub(crate) async fn fetch_homework(
+    user: &UsernamePassword,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+) -> anyhow::Result<Vec<Homework>> {
+    let lookback = from
+        .checked_sub_days(chrono::Days::new(HOMEWORK_LOOKBACK_DAYS))
+        .ok_or(DateOutOfRange)?;
+    let client = build_client();
+
+    info!("Logging in as {}...", &user.username);
+    let userinfo = login(&client, &user.username, &user.password).await?;
+    let subjects = get_elements(&client, &userinfo.session_id, ElementType::Subject)
+        .await
+        .inspect_err(|err| info!("Could not retrieve subject list: {err}"))
+        .unwrap_or_default();
+    info!("Retrieving homework...");
+    let data = get_homework(&client, &userinfo.session_id, lookback, to).await;
+    info!("Logging out...");
+    logout(&client, &userinfo.session_id).await?;
+    let data = data?;
+
+    let lessons = data
+        .lessons
+        .iter()
+        .map(|lesson| (lesson.id, lesson.subject.as_str()))
+        .collect::<HashMap<u64, &str>>();
+    let teachers = data
+        .teachers
+        .iter()
+        .map(|teacher| (teacher.id, teacher.name.as_str()))
+        .collect::<HashMap<u64, &str>>();
+    let homework_teachers = data
+        .records
+        .iter()
+        .filter_map(|record| Some((record.homework_id, *teachers.get(&record.teacher_id?)?)))
+        .collect::<HashMap<u64, &str>>();
+    let mut homework = vec![];
+    for info in data.homeworks {
+        let due_date = untis_date(info.due_date)?;
+        if due_date < from || due_date > to {
+            continue;
+        }
+        homework.push(Homework {
+            id: info.id,
+            subject: lessons
+                .get(&info.lesson_id)
+                .filter(|subject| !subject.is_empty())
+                .map(|subject| linked_element(&subjects, None, subject)),
+            teacher: homework_teachers.get(&info.id).map(|name| name.to_string()),
+            date: untis_date(info.date)?,
+            due_date,
+            text: info.text,
+            remark: info.remark,
+            completed: info.completed,
+        });
+    }
+    homework.sort_by_key(|homework| (homework.due_date, homework.date));
+    Ok(homework)
+}
@@ -0,0 +1,466 @@
+use reqwest::header::COOKIE;
+use reqwest::Client;
+use rocket::serde::json::serde_json;
+use rocket::serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::speech::Speech;
+use crate::untis::substitutions::rpc_date;
+use crate::untis::{build_client, login, logout, UsernamePassword};
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct MessageSenderInfo {
+    #[serde(rename = "displayName")]
+    pub(crate) display_name: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct MessageListEntry {
+    pub(crate) id: u64,
+    #[serde(default)]
+    pub(crate) subject: String,
+    #[serde(default, rename = "contentPreview")]
+    content_preview: String,
+    #[serde(rename = "sentDateTime")]
+    pub(crate) sent: Option<String>,
+    pub(crate) sender: Option<MessageSenderInfo>,
+    #[serde(default, rename = "isMessageRead")]
+    is_read: bool,
+    #[serde(default, rename = "hasAttachments")]
+    has_attachments: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct MessageListResponse {
+    #[serde(default, rename = "incomingMessages")]
+    pub(crate) incoming: Vec<MessageListEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct MessageAttachmentInfo {
+    /// Numeric for attachments, a UUID for files in the school's storage
+    pub(crate) id: serde_json::Value,
+    pub(crate) name: String,
+    #[serde(rename = "contentType")]
+    pub(crate) content_type: Option<String>,
+    size: Option<u64>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct MessageInfo {
+    pub(crate) id: u64,
+    #[serde(default)]
+    pub(crate) subject: String,
+    #[serde(default)]
+    pub(crate) content: String,
+    #[serde(rename = "sentDateTime")]
+    pub(crate) sent: Option<String>,
+    pub(crate) sender: Option<MessageSenderInfo>,
+    #[serde(default)]
+    pub(crate) attachments: Vec<MessageAttachmentInfo>,
+    #[serde(default, rename = "storageAttachments")]
+    storage_attachments: Vec<MessageAttachmentInfo>,
+}
+
+/// A message in the inbox, without its content
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct MessageSummary {
+    pub(crate) id: u64,
+    pub(crate) subject: String,
+    pub(crate) preview: String,
+    pub(crate) sender: Option<String>,
+    pub(crate) sent: Option<chrono::NaiveDateTime>,
+    pub(crate) read: bool,
+    #[serde(rename = "hasAttachments")]
+    has_attachments: bool,
+}
+
+impl From<MessageListEntry> for MessageSummary {
+    fn from(message: MessageListEntry) -> Self {
+        MessageSummary {
+            id: message.id,
+            subject: message.subject,
+            preview: message.content_preview,
+            sender: message.sender.map(|sender| sender.display_name),
+            sent: message.sent.as_deref().and_then(message_time),
+            read: message.is_read,
+            has_attachments: message.has_attachments,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Inbox {
+    pub(crate) unread: usize,
+    pub(crate) messages: Vec<MessageSummary>,
+}
+
+/// Metadata of a file attached to a message. The file itself is not retrieved.
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct MessageAttachment {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    #[serde(rename = "contentType")]
+    pub(crate) content_type: Option<String>,
+    size: Option<u64>,
+}
+
+impl From<MessageAttachmentInfo> for MessageAttachment {
+    fn from(attachment: MessageAttachmentInfo) -> Self {
+        MessageAttachment {
+            id: match attachment.id {
+                serde_json::Value::String(id) => id,
+                id => id.to_string(),
+            },
+            name: attachment.name,
+            content_type: attachment.content_type,
+            size: attachment.size,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Message {
+    pub(crate) id: u64,
+    pub(crate) subject: String,
+    pub(crate) sender: Option<String>,
+    pub(crate) sent: Option<chrono::NaiveDateTime>,
+    /// HTML, as written in the messenger
+    pub(crate) content: String,
+    /// The content as plain text
+    pub(crate) text: String,
+    pub(crate) attachments: Vec<MessageAttachment>,
+}
+
+impl From<MessageInfo> for Message {
+    fn from(message: MessageInfo) -> Self {
+        Message {
+            id: message.id,
+            subject: message.subject,
+            sender: message.sender.map(|sender| sender.display_name),
+            sent: message.sent.as_deref().and_then(message_time),
+            text: html_text(&message.content),
+            content: message.content,
+            attachments: message
+                .attachments
+                .into_iter()
+                .chain(message.storage_attachments)
+                .map(MessageAttachment::from)
+                .collect(),
+        }
+    }
+}
+
+/// Parses the send time of a message, e.g. `2025-03-17T07:45` (with or without seconds)
+fn message_time(time: &str) -> Option<chrono::NaiveDateTime> {
+    let time = time.trim_end_matches('Z');
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| chrono::NaiveDateTime::parse_from_str(time, format).ok())
+}
+
+/// The text of a message written in HTML, with paragraphs and line breaks kept. Styles and
+/// scripts are dropped.
+pub(crate) fn html_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = rest[start + 1..start + end].to_lowercase();
+        let name = tag
+            .split_whitespace()
+            .next()
+            .unwrap_or("")
+            .trim_end_matches('/');
+        if ["br", "/p", "/div", "/li"].contains(&name) {
+            text.push('\n');
+        }
+        rest = &rest[start + end + 1..];
+        if ["style", "script"].contains(&name) && !tag.ends_with('/') {
+            // Their content is not text, skip to the closing tag
+            rest = match rest.to_ascii_lowercase().find(&format!("</{name}")) {
+                Some(close) => &rest[close..],
+                None => "",
+            };
+        }
+    }
+    text.push_str(rest);
+    let text = decode_html_entities(&text);
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .collect::<Vec<String>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Replaces numeric character references (`&#228;`, `&#xE4;`) and common named entities
+/// (`&auml;`) by their characters. Unknown entities are kept as they are.
+fn decode_html_entities(text: &str) -> String {
+    const NAMED: &[(&str, &str)] = &[
+        ("amp", "&"),
+        ("lt", "<"),
+        ("gt", ">"),
+        ("quot", "\""),
+        ("apos", "'"),
+        ("nbsp", " "),
+        ("shy", ""),
+        ("auml", "ä"),
+        ("ouml", "ö"),
+        ("uuml", "ü"),
+        ("Auml", "Ä"),
+        ("Ouml", "Ö"),
+        ("Uuml", "Ü"),
+        ("szlig", "ß"),
+        ("eacute", "é"),
+        ("egrave", "è"),
+        ("aacute", "á"),
+        ("agrave", "à"),
+        ("euro", "€"),
+        ("sect", "§"),
+        ("deg", "°"),
+        ("copy", "©"),
+        ("middot", "·"),
+        ("bull", "•"),
+        ("ndash", "–"),
+        ("mdash", "—"),
+        ("hellip", "…"),
+        ("bdquo", "„"),
+        ("ldquo", "“"),
+        ("rdquo", "”"),
+        ("sbquo", "‚"),
+        ("lsquo", "‘"),
+        ("rsquo", "’"),
+        ("laquo", "«"),
+        ("raquo", "»"),
+    ];
+
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 10)
+            .map(|end| &rest[1..end + 1]);
+        let character = entity.and_then(|entity| match entity.strip_prefix('#') {
+            Some(number) => match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => number.parse().ok(),
+            }
+            .and_then(char::from_u32)
+            .map(String::from),
+            None => NAMED
+                .iter()
+                .find(|(name, _)| *name == entity)
+                .map(|(_, character)| character.to_string()),
+        });
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push_str(&character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// The messenger requires a bearer token instead of the session cookie
+async fn get_messenger_token(client: &Client, jsession_id: &str) -> anyhow::Result<String> {
+    let response = client
+        .get(format!(
+            "https://{}/WebUntis/api/token/new",
+            std::env::var("UNTIS_HOST").expect("'UNTIS_HOST' not defined!")
+        ))
+        .header(COOKIE, format!("JSESSIONID={jsession_id}"))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(response.text().await?)
+}
+
+/// Retrieves a resource of the messenger, e.g. `messages` or `messages/42`
+async fn get_messenger<T: rocket::serde::DeserializeOwned>(
+    client: &Client,
+    jsession_id: &str,
+    path: &str,
+) -> anyhow::Result<T> {
+    let token = get_messenger_token(client, jsession_id).await?;
+    debug!("Retrieving {path} from messenger...");
+    let response = client
+        .get(format!(
+            "https://{}/WebUntis/api/rest/view/v1/{path}",
+            std::env::var("UNTIS_HOST").expect("'UNTIS_HOST' not defined!")
+        ))
+        .header(COOKIE, format!("JSESSIONID={jsession_id}"))
+        .bearer_auth(token)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(response.json().await?)
+}
+
+/// Logs in and retrieves the incoming messages, newest first
+pub(crate) async fn fetch_inbox(user: &UsernamePassword) -> anyhow::Result<Inbox> {
+    let client = build_client();
+
+    info!("Logging in as {}...", &user.username);
+    let userinfo = login(&client, &user.username, &user.password).await?;
+    info!("Retrieving messages...");
+    let messages =
+        get_messenger::<MessageListResponse>(&client, &userinfo.session_id, "messages").await;
+    info!("Logging out...");
+    logout(&client, &userinfo.session_id).await?;
+
+    let mut messages = messages?
+        .incoming
+        .into_iter()
+        .map(MessageSummary::from)
+        .collect::<Vec<MessageSummary>>();
+    messages.sort_by_key(|message| std::cmp::Reverse(message.sent));
+    Ok(Inbox {
+        unread: messages.iter().filter(|message| !message.read).count(),
+        messages,
+    })
+}
+
+/// Logs in and retrieves a message with its content. WebUntis may mark the message as read.
+pub(crate) async fn fetch_message(user: &UsernamePassword, id: u64) -> anyhow::Result<Message> {
+    let client = build_client();
+
+    info!("Logging in as {}...", &user.username);
+    let userinfo = login(&client, &user.username, &user.password).await?;
+    info!("Retrieving message {id}...");
+    let message =
+        get_messenger::<MessageInfo>(&client, &userinfo.session_id, &format!("messages/{id}"))
+            .await;
+    info!("Logging out...");
+    logout(&client, &userinfo.session_id).await?;
+    Ok(Message::from(message?))
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct DayMessageInfo {
+    pub(crate) id: u64,
+    #[serde(default)]
+    pub(crate) subject: String,
+    #[serde(default)]
+    pub(crate) text: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct NewsWidgetData {
+    #[serde(default, rename = "messagesOfDay")]
+    messages_of_day: Vec<DayMessageInfo>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct NewsWidgetResponse {
+    pub(crate) data: NewsWidgetData,
+}
+
+/// A notice of the school for a single day ("Nachricht zum Tag"), e.g. about early dismissal
+#[derive(Serialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct DayMessage {
+    pub(crate) id: u64,
+    pub(crate) subject: String,
+    /// Plain text, stripped of the HTML of the news widget
+    pub(crate) text: String,
+}
+
+impl DayMessage {
+    /// "Nachricht zum Tag: Unterrichtsende. Der Unterricht endet heute um 11:30 Uhr."
+    pub(crate) fn speakable(&self, speech: Speech) -> String {
+        let text = speech.text(&self.text.replace('\n', " "));
+        if self.subject.trim().is_empty() {
+            speech.translate("day-message-untitled", &[("text", &text)])
+        } else {
+            speech.translate(
+                "day-message",
+                &[
+                    ("subject", &speech.text(self.subject.trim())),
+                    ("text", &text),
+                ],
+            )
+        }
+    }
+}
+
+/// Retrieves the messages of the day from the news widget
+async fn get_day_messages(
+    client: &Client,
+    jsession_id: &str,
+    date: chrono::NaiveDate,
+) -> anyhow::Result<Vec<DayMessage>> {
+    debug!("Retrieving messages of {date}...");
+    let response = client
+        .get(format!(
+            "https://{}/WebUntis/api/public/news/newsWidgetData?date={}",
+            std::env::var("UNTIS_HOST").expect("'UNTIS_HOST' not defined!"),
+            rpc_date(date)
+        ))
+        .header(COOKIE, format!("JSESSIONID={jsession_id}"))
+        .send()
+        .await?
+        .error_for_status()?;
+    let news: NewsWidgetResponse = response.json().await?;
+    Ok(news
+        .data
+        .messages_of_day
+        .into_iter()
+        .map(|message| DayMessage {
+            id: message.id,
+            subject: html_text(&message.subject),
+            text: html_text(&message.text),
+        })
+        .filter(|message| !message.subject.is_empty() || !message.text.is_empty())
+        .collect())
+}
+
+/// Logs in and retrieves the messages of each of the days
+pub(crate) async fn fetch_day_messages(
+    user: &UsernamePassword,
+    dates: &[chrono::NaiveDate],
+) -> anyhow::Result<HashMap<chrono::NaiveDate, Vec<DayMessage>>> {
+    let client = build_client();
+
+    info!("Logging in as {}...", &user.username);
+    let userinfo = login(&client, &user.username, &user.password).await?;
+    let messages = async {
+        let mut messages = HashMap::new();
+        for date in dates {
+            messages.insert(
+                *date,
+                get_day_messages(&client, &userinfo.session_id, *date).await?,
+            );
+        }
+        anyhow::Ok(messages)
+    }
+    .await;
+    info!("Logging out...");
+    logout(&client, &userinfo.session_id).await?;
+    messages
+}
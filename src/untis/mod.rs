@@ -0,0 +1,288 @@
+pub(crate) mod absences;
+pub(crate) mod availability;
+pub(crate) mod calendar;
+pub(crate) mod exams;
+pub(crate) mod homework;
+pub(crate) mod messages;
+pub(crate) mod substitutions;
+pub(crate) mod timegrid;
+pub(crate) mod timetable;
+
+use anyhow::anyhow;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, COOKIE};
+use reqwest::{Client, Error, Response};
+use rocket::http::Status;
+use rocket::serde::json::serde_json::{self, json};
+use rocket::serde::uuid::Uuid;
+use rocket::serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::speech::TEACHER_SURNAMES;
+use crate::untis::availability::active_default;
+
+pub(crate) enum RPCMethods {
+    Authenticate,
+    Logout,
+    GetTimegridUnits,
+    GetTeachers,
+    GetSubstitutions,
+    GetHolidays,
+    GetCurrentSchoolyear,
+    GetSchoolyears,
+    GetExamTypes,
+    GetExams,
+    GetTimetableWithAbsences,
+    GetRooms,
+}
+
+pub(crate) async fn request(
+    client: &Client,
+    method: RPCMethods,
+    params: serde_json::Value,
+    jsession_id: Option<&str>,
+) -> Result<(Uuid, Response), Error> {
+    #[derive(Serialize)]
+    #[serde(crate = "rocket::serde")]
+    struct Body<'a> {
+        id: Uuid,
+        method: &'a str,
+        params: serde_json::Value,
+        jsonrpc: &'static str,
+    }
+
+    let uid = Uuid::new_v4();
+
+    let body = Body {
+        id: uid,
+        method: match method {
+            RPCMethods::Authenticate => "authenticate",
+            RPCMethods::Logout => "logout",
+            RPCMethods::GetTimegridUnits => "getTimegridUnits",
+            RPCMethods::GetTeachers => "getTeachers",
+            RPCMethods::GetSubstitutions => "getSubstitutions",
+            RPCMethods::GetHolidays => "getHolidays",
+            RPCMethods::GetCurrentSchoolyear => "getCurrentSchoolyear",
+            RPCMethods::GetSchoolyears => "getSchoolyears",
+            RPCMethods::GetExamTypes => "getExamTypes",
+            RPCMethods::GetExams => "getExams",
+            RPCMethods::GetTimetableWithAbsences => "getTimetableWithAbsences",
+            RPCMethods::GetRooms => "getRooms",
+        },
+        jsonrpc: "2.0",
+        params,
+    };
+
+    let mut request = client
+        .post(format!(
+            "https://{}/WebUntis/jsonrpc.do?school={}",
+            std::env::var("UNTIS_HOST").expect("'UNTIS_HOST' not defined!"),
+            std::env::var("UNTIS_SCHOOL").expect("'UNTIS_SCHOOL' not defined!")
+        ))
+        .json(&body);
+
+    if let Some(id) = jsession_id {
+        request = request.header(COOKIE, format!("JSESSIONID={}", id));
+    }
+
+    let response = request.send().await?;
+    Ok((uid, response))
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct RPCResponse<T> {
+    #[allow(dead_code)]
+    jsonrpc: String,
+    pub(crate) id: Uuid,
+    pub(crate) result: Option<T>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct UserInfo {
+    #[serde(rename = "sessionId")]
+    pub(crate) session_id: String,
+    #[allow(dead_code)]
+    #[serde(rename = "personType")]
+    person_type: u64,
+    #[serde(rename = "personId")]
+    pub(crate) person_id: u64,
+    #[serde(rename = "klasseId")]
+    pub(crate) klasse_id: u64,
+}
+
+pub(crate) async fn login(client: &Client, user: &str, password: &str) -> anyhow::Result<UserInfo> {
+    debug!("Logging in to webuntis as {user}");
+    let result: Option<UserInfo> = rpc_response(
+        client,
+        RPCMethods::Authenticate,
+        json!({
+            "user": user,
+            "password": password,
+            "client": concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"))
+        }),
+        None,
+    )
+    .await?;
+    debug!("Log in result: {result:?}");
+    result.ok_or(LoginFailed.into())
+}
+
+/// WebUntis refused the credentials
+#[derive(Debug)]
+struct LoginFailed;
+
+impl std::fmt::Display for LoginFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Could not log in, WebUntis refused the credentials")
+    }
+}
+
+impl std::error::Error for LoginFailed {}
+
+/// The response status for a request that failed talking to WebUntis: `401 Unauthorized` if the
+/// credentials were refused, `502 Bad Gateway` otherwise
+pub(crate) fn upstream_status(err: &anyhow::Error) -> Status {
+    if err.is::<LoginFailed>() {
+        Status::Unauthorized
+    } else {
+        Status::BadGateway
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct TeacherInfo {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    #[serde(default, rename = "foreName")]
+    pub(crate) fore_name: String,
+    #[serde(rename = "longName")]
+    pub(crate) long_name: String,
+    #[serde(default = "active_default")]
+    pub(crate) active: bool,
+}
+
+/// Retrieves the surnames of all teachers once per account, if the account is permitted to. A
+/// refused request is remembered as well, so that it is not repeated for every timetable.
+pub(crate) async fn get_teacher_surnames(
+    client: &Client,
+    jsession_id: &str,
+    account: &str,
+) -> anyhow::Result<()> {
+    if TEACHER_SURNAMES
+        .read()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|accounts| accounts.contains_key(account))
+    {
+        return Ok(());
+    }
+
+    debug!("Retrieving teachers...");
+    // Only a missing result means the account is not permitted, other errors are not remembered
+    let teachers: Option<Vec<TeacherInfo>> = rpc_response(
+        client,
+        RPCMethods::GetTeachers,
+        serde_json::Value::Null,
+        Some(jsession_id),
+    )
+    .await?;
+    let surnames = teachers.map(|teachers| {
+        teachers
+            .into_iter()
+            .filter(|teacher| !teacher.long_name.is_empty())
+            .map(|teacher| (teacher.name, teacher.long_name))
+            .collect()
+    });
+    let permitted = surnames.is_some();
+    TEACHER_SURNAMES
+        .write()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(account.to_string(), surnames);
+    if !permitted {
+        return Err(anyhow!(
+            "Result Type is empty! Could not retrieve teachers!"
+        ));
+    }
+    Ok(())
+}
+
+/// Calls a JSON-RPC method and returns its result, `None` if WebUntis did not return one (usually
+/// because the account is not permitted to call the method)
+async fn rpc_response<T: rocket::serde::DeserializeOwned>(
+    client: &Client,
+    method: RPCMethods,
+    params: serde_json::Value,
+    jsession_id: Option<&str>,
+) -> anyhow::Result<Option<T>> {
+    let (uid, response) = request(client, method, params, jsession_id).await?;
+    let data: RPCResponse<T> = response.json().await?;
+    if data.id != uid {
+        return Err(anyhow!(
+            "Response id {} does not match request id {uid}",
+            data.id
+        ));
+    }
+    Ok(data.result)
+}
+
+/// Calls a JSON-RPC method and returns its result, failing if there is none
+pub(crate) async fn rpc_result<T: rocket::serde::DeserializeOwned>(
+    client: &Client,
+    method: RPCMethods,
+    params: serde_json::Value,
+    jsession_id: &str,
+    what: &str,
+) -> anyhow::Result<T> {
+    rpc_response(client, method, params, Some(jsession_id))
+        .await?
+        .ok_or(anyhow!("Result Type is empty! Could not retrieve {what}!"))
+}
+
+pub(crate) async fn logout(client: &Client, jsession_id: &str) -> anyhow::Result<()> {
+    rpc_response::<()>(
+        client,
+        RPCMethods::Logout,
+        serde_json::Value::Null,
+        Some(jsession_id),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Converts a time as sent by WebUntis (e.g. `745` for 07:45) into a [`chrono::NaiveTime`]
+pub(crate) fn untis_time(value: u64) -> anyhow::Result<chrono::NaiveTime> {
+    chrono::NaiveTime::from_hms_opt((value / 100) as u32, (value % 100) as u32, 0)
+        .ok_or(anyhow!("Invalid time '{value}'"))
+}
+
+/// Converts a date as sent by WebUntis (e.g. `20250317`) into a [`chrono::NaiveDate`]
+pub(crate) fn untis_date(value: u64) -> anyhow::Result<chrono::NaiveDate> {
+    Ok(chrono::NaiveDate::parse_from_str(
+        &value.to_string(),
+        "%Y%m%d",
+    )?)
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct UsernamePassword {
+    pub(crate) username: String,
+    pub(crate) password: String,
+}
+
+pub(crate) fn build_client() -> Client {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    Client::builder()
+        .user_agent(concat!(
+            env!("CARGO_PKG_NAME"),
+            "/",
+            env!("CARGO_PKG_VERSION")
+        ))
+        .default_headers(headers)
+        .build()
+        .unwrap()
+}
@@ -0,0 +1,266 @@
+use chrono::Datelike;
+use reqwest::Client;
+use rocket::serde::json::serde_json::json;
+use rocket::serde::{Deserialize, Serialize};
+
+use crate::untis::timetable::{
+    ElementState, Klasse, OriginalRoom, OriginalTeacher, PeriodState, Room, Subject, Teacher,
+};
+use crate::untis::{rpc_result, untis_date, untis_time, RPCMethods};
+
+/// Element reference in the result of `getSubstitutions`. Teachers and rooms carry the replaced
+/// element in `orgid`/`orgname`.
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct SubstitutionElement {
+    pub(crate) id: u64,
+    #[serde(default)]
+    pub(crate) name: String,
+    #[serde(rename = "longname")]
+    pub(crate) long_name: Option<String>,
+    #[serde(rename = "orgid")]
+    pub(crate) original_id: Option<u64>,
+    #[serde(rename = "orgname")]
+    original_name: Option<String>,
+}
+
+impl SubstitutionElement {
+    pub(crate) fn state(&self) -> ElementState {
+        match (self.id, self.original_id) {
+            (0, Some(_)) => ElementState::Absent,
+            (_, Some(_)) => ElementState::Substituted,
+            (_, None) => ElementState::Regular,
+        }
+    }
+
+    pub(crate) fn long_name(&self) -> String {
+        self.long_name
+            .clone()
+            .unwrap_or_else(|| self.name.to_string())
+    }
+}
+
+impl From<SubstitutionElement> for Klasse {
+    fn from(element: SubstitutionElement) -> Self {
+        Klasse {
+            id: element.id,
+            original_klasse_id: 0,
+            original_klasse: None,
+            missing: false,
+            state: element.state(),
+            long_name: element.long_name(),
+            displayname: element.name.to_string(),
+            alternatename: String::new(),
+            name: element.name,
+            can_view_timetable: false,
+            room_capacity: 0,
+        }
+    }
+}
+
+impl From<SubstitutionElement> for Teacher {
+    fn from(element: SubstitutionElement) -> Self {
+        Teacher {
+            id: element.id,
+            original_teacher_id: element.original_id.unwrap_or(0),
+            original_teacher: element.original_id.map(|id| OriginalTeacher {
+                id,
+                name: element.original_name.clone().unwrap_or_default(),
+                can_view_timetable: false,
+                extern_key: String::new(),
+                room_capacity: 0,
+            }),
+            missing: false,
+            state: element.state(),
+            name: element.name,
+            can_view_timetable: false,
+            extern_key: String::new(),
+            room_capacity: 0,
+        }
+    }
+}
+
+impl From<SubstitutionElement> for Subject {
+    fn from(element: SubstitutionElement) -> Self {
+        Subject {
+            id: element.id,
+            original_subject_id: 0,
+            original_subject: None,
+            missing: false,
+            state: element.state(),
+            long_name: element.long_name(),
+            display_name: element.name.to_string(),
+            alternate_name: String::new(),
+            name: element.name,
+            back_color: String::new(),
+            can_view_timetable: false,
+            room_capacity: 0,
+            fore_color: None,
+        }
+    }
+}
+
+impl From<SubstitutionElement> for Room {
+    fn from(element: SubstitutionElement) -> Self {
+        Room {
+            id: element.id,
+            original_room_id: element.original_id.unwrap_or(0),
+            original_room: element.original_id.map(|id| {
+                let name = element.original_name.clone().unwrap_or_default();
+                OriginalRoom {
+                    id,
+                    long_name: name.to_string(),
+                    displayname: name.to_string(),
+                    alternatename: String::new(),
+                    name,
+                    can_view_timetable: false,
+                    room_capacity: 0,
+                }
+            }),
+            missing: false,
+            state: element.state(),
+            long_name: element.long_name(),
+            displayname: element.name.to_string(),
+            alternatename: String::new(),
+            name: element.name,
+            can_view_timetable: false,
+            room_capacity: 0,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct SubstitutionReschedule {
+    pub(crate) date: u64,
+    #[serde(rename = "startTime")]
+    pub(crate) start_time: u64,
+    #[serde(rename = "endTime")]
+    pub(crate) end_time: u64,
+}
+
+/// A substitution as returned by `getSubstitutions`
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct SubstitutionInfo {
+    /// `cancel`, `subst`, `add`, `shift`, `rmchg`, `rmlk`, `bs`, `free` or `exam`
+    #[serde(rename = "type")]
+    substitution_type: String,
+    #[serde(rename = "lsid")]
+    pub(crate) lesson_id: Option<u64>,
+    pub(crate) date: u64,
+    #[serde(rename = "startTime")]
+    pub(crate) start_time: u64,
+    #[serde(rename = "endTime")]
+    pub(crate) end_time: u64,
+    #[serde(default, rename = "kl")]
+    pub(crate) klassen: Vec<SubstitutionElement>,
+    #[serde(default, rename = "te")]
+    pub(crate) teachers: Vec<SubstitutionElement>,
+    #[serde(default, rename = "su")]
+    pub(crate) subjects: Vec<SubstitutionElement>,
+    #[serde(default, rename = "ro")]
+    pub(crate) rooms: Vec<SubstitutionElement>,
+    #[serde(rename = "txt")]
+    pub(crate) text: Option<String>,
+    reschedule: Option<SubstitutionReschedule>,
+}
+
+/// Where a shifted lesson takes place instead
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct Reschedule {
+    pub(crate) date: chrono::NaiveDate,
+    #[serde(rename = "startTime")]
+    pub(crate) start_time: chrono::NaiveTime,
+    #[serde(rename = "endTime")]
+    pub(crate) end_time: chrono::NaiveTime,
+}
+
+/// A change of the school-wide substitution plan
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Substitution {
+    #[serde(rename = "lessonId")]
+    pub(crate) lesson_id: Option<u64>,
+    pub(crate) date: chrono::NaiveDate,
+    #[serde(rename = "startTime")]
+    pub(crate) start_time: chrono::NaiveTime,
+    #[serde(rename = "endTime")]
+    pub(crate) end_time: chrono::NaiveTime,
+    pub(crate) state: PeriodState,
+    /// The type of substitution as WebUntis calls it, as some have no matching [`PeriodState`]
+    #[serde(rename = "substitutionType")]
+    substitution_type: String,
+    pub(crate) klassen: Vec<Klasse>,
+    pub(crate) teachers: Vec<Teacher>,
+    pub(crate) subjects: Vec<Subject>,
+    pub(crate) rooms: Vec<Room>,
+    pub(crate) text: String,
+    reschedule: Option<Reschedule>,
+}
+
+impl TryFrom<SubstitutionInfo> for Substitution {
+    type Error = anyhow::Error;
+
+    fn try_from(info: SubstitutionInfo) -> anyhow::Result<Self> {
+        Ok(Substitution {
+            lesson_id: info.lesson_id,
+            date: untis_date(info.date)?,
+            start_time: untis_time(info.start_time)?,
+            end_time: untis_time(info.end_time)?,
+            state: match info.substitution_type.as_str() {
+                "cancel" => PeriodState::Cancel,
+                "subst" => PeriodState::Substitution,
+                "add" => PeriodState::Additional,
+                "rmchg" => PeriodState::RoomSubstitution,
+                state => PeriodState::Unknown(state.to_string()),
+            },
+            substitution_type: info.substitution_type,
+            klassen: info.klassen.into_iter().map(Klasse::from).collect(),
+            teachers: info.teachers.into_iter().map(Teacher::from).collect(),
+            subjects: info.subjects.into_iter().map(Subject::from).collect(),
+            rooms: info.rooms.into_iter().map(Room::from).collect(),
+            text: info.text.unwrap_or_default(),
+            reschedule: match info.reschedule {
+                Some(reschedule) => Some(Reschedule {
+                    date: untis_date(reschedule.date)?,
+                    start_time: untis_time(reschedule.start_time)?,
+                    end_time: untis_time(reschedule.end_time)?,
+                }),
+                None => None,
+            },
+        })
+    }
+}
+
+/// Dates as JSON-RPC methods expect them, e.g. `20250317`
+pub(crate) fn rpc_date(date: chrono::NaiveDate) -> u64 {
+    date.year() as u64 * 10000 + date.month() as u64 * 100 + date.day() as u64
+}
+
+/// Retrieves the substitutions of the whole school between `start` and `end` (inclusive)
+pub(crate) async fn get_substitutions(
+    client: &Client,
+    jsession_id: &str,
+    start: chrono::NaiveDate,
+    end: chrono::NaiveDate,
+) -> anyhow::Result<Vec<Substitution>> {
+    debug!("Retrieving substitutions from {start} to {end}...");
+    let substitutions: Vec<SubstitutionInfo> = rpc_result(
+        client,
+        RPCMethods::GetSubstitutions,
+        json!({
+            "startDate": rpc_date(start),
+            "endDate": rpc_date(end),
+            "departmentId": 0,
+        }),
+        jsession_id,
+        "substitutions",
+    )
+    .await?;
+    substitutions
+        .into_iter()
+        .map(Substitution::try_from)
+        .collect()
+}
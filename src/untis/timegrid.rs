@@ -0,0 +1,135 @@
+use anyhow::anyhow;
+use chrono::Datelike;
+use reqwest::Client;
+use rocket::serde::json::serde_json;
+use rocket::serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::untis::timetable::Period;
+use crate::untis::{rpc_result, untis_time, RPCMethods};
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct TimegridDay {
+    /// 1 is Sunday, 2 is Monday, ...
+    pub(crate) day: u32,
+    #[serde(rename = "timeUnits")]
+    time_units: Vec<TimegridUnit>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct TimegridUnit {
+    pub(crate) name: String,
+    #[serde(rename = "startTime")]
+    pub(crate) start_time: u64,
+    #[serde(rename = "endTime")]
+    pub(crate) end_time: u64,
+}
+
+/// The period numbers (e.g. "3. Stunde") a period spans
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct PeriodNumbers {
+    pub(crate) first: u32,
+    pub(crate) last: u32,
+}
+
+/// The school's time grid, i.e. which period ("Stunde") starts and ends when on each weekday
+pub(crate) struct Timegrid {
+    pub(crate) days: HashMap<chrono::Weekday, Vec<(u32, chrono::NaiveTime, chrono::NaiveTime)>>,
+}
+
+impl Timegrid {
+    pub(crate) fn from_days(days: Vec<TimegridDay>) -> anyhow::Result<Self> {
+        let mut timegrid = Timegrid {
+            days: HashMap::new(),
+        };
+        for day in days {
+            let weekday = match day.day {
+                1 => chrono::Weekday::Sun,
+                2 => chrono::Weekday::Mon,
+                3 => chrono::Weekday::Tue,
+                4 => chrono::Weekday::Wed,
+                5 => chrono::Weekday::Thu,
+                6 => chrono::Weekday::Fri,
+                7 => chrono::Weekday::Sat,
+                day => return Err(anyhow!("Unknown day '{day}' in time grid")),
+            };
+            let mut units = vec![];
+            for (index, unit) in day.time_units.into_iter().enumerate() {
+                // Unit names are usually the period numbers, fall back to counting otherwise
+                let number = unit.name.trim().parse().unwrap_or(index as u32 + 1);
+                units.push((
+                    number,
+                    untis_time(unit.start_time)?,
+                    untis_time(unit.end_time)?,
+                ));
+            }
+            timegrid.days.insert(weekday, units);
+        }
+        Ok(timegrid)
+    }
+
+    /// The periods, `period` overlaps with. Double periods span two (or more) units.
+    pub(crate) fn period_numbers(&self, period: &Period) -> Option<PeriodNumbers> {
+        self.numbers(period.date, period.start_time, period.end_time)
+    }
+
+    /// When the periods `numbers` start and end on `date`, if there are such periods
+    pub(crate) fn span(
+        &self,
+        date: chrono::NaiveDate,
+        numbers: PeriodNumbers,
+    ) -> Option<(chrono::NaiveTime, chrono::NaiveTime)> {
+        let units = self.days.get(&date.weekday())?;
+        let unit = |number: u32| units.iter().find(|(unit, _, _)| *unit == number);
+        let (_, start, _) = unit(numbers.first)?;
+        let (_, _, end) = unit(numbers.last)?;
+        Some((*start, *end))
+    }
+
+    /// The periods, the time from `start` to `end` on `date` overlaps with
+    pub(crate) fn numbers(
+        &self,
+        date: chrono::NaiveDate,
+        start: chrono::NaiveTime,
+        end: chrono::NaiveTime,
+    ) -> Option<PeriodNumbers> {
+        let units = self.days.get(&date.weekday())?;
+        let mut overlapping = units
+            .iter()
+            .filter(|(_, unit_start, unit_end)| *unit_start < end && start < *unit_end)
+            .map(|(number, _, _)| *number);
+        let first = overlapping.next()?;
+        let last = overlapping.next_back().unwrap_or(first);
+        Some(PeriodNumbers { first, last })
+    }
+}
+
+/// Retrieves the time grid once and caches it for the lifetime of the service. Failures are not
+/// cached, so that a later request can try again.
+pub(crate) async fn get_timegrid(
+    client: &Client,
+    jsession_id: &str,
+) -> anyhow::Result<&'static Timegrid> {
+    static TIMEGRID: OnceLock<Timegrid> = OnceLock::new();
+    if let Some(timegrid) = TIMEGRID.get() {
+        return Ok(timegrid);
+    }
+
+    debug!("Retrieving time grid...");
+    let days: Vec<TimegridDay> = rpc_result(
+        client,
+        RPCMethods::GetTimegridUnits,
+        serde_json::Value::Null,
+        jsession_id,
+        "time grid",
+    )
+    .await?;
+    let timegrid = Timegrid::from_days(days).inspect_err(|err| {
+        error!("Could not read time grid: {err}");
+    })?;
+    Ok(TIMEGRID.get_or_init(|| timegrid))
+}
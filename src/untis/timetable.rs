@@ -0,0 +1,929 @@
+use anyhow::anyhow;
+use reqwest::header::COOKIE;
+use reqwest::{Client, Error};
+use rocket::serde::json::serde_json;
+use rocket::serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::speech::lookup_teacher_names;
+use crate::untis::timegrid::{get_timegrid, PeriodNumbers, Timegrid};
+use crate::untis::{
+    build_client, get_teacher_surnames, login, logout, untis_date, untis_time, UsernamePassword,
+};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub(crate) enum ElementState {
+    Regular,
+    Absent,
+    Substituted,
+    Unknown(String),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct OriginalKlasse {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    #[serde(rename = "longName")]
+    pub(crate) long_name: String,
+    pub(crate) displayname: String,
+    pub(crate) alternatename: String,
+    #[serde(rename = "canViewTimetable")]
+    pub(crate) can_view_timetable: bool,
+    #[serde(rename = "roomCapacity")]
+    pub(crate) room_capacity: u64,
+}
+
+impl From<&OriginalKlasse> for OriginalKlasse {
+    fn from(val: &OriginalKlasse) -> Self {
+        Self {
+            id: val.id,
+            name: String::from(&val.name),
+            long_name: String::from(&val.long_name),
+            displayname: String::from(&val.displayname),
+            alternatename: String::from(&val.alternatename),
+            can_view_timetable: val.can_view_timetable,
+            room_capacity: val.room_capacity,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Klasse {
+    pub(crate) id: u64,
+    #[serde(rename = "orgId")]
+    pub(crate) original_klasse_id: u64,
+    pub(crate) original_klasse: Option<OriginalKlasse>,
+    pub(crate) missing: bool,
+    pub(crate) state: ElementState,
+    pub(crate) name: String,
+    #[serde(rename = "longName")]
+    pub(crate) long_name: String,
+    pub(crate) displayname: String,
+    pub(crate) alternatename: String,
+    #[serde(rename = "canViewTimetable")]
+    pub(crate) can_view_timetable: bool,
+    #[serde(rename = "roomCapacity")]
+    pub(crate) room_capacity: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct OriginalRoom {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    #[serde(rename = "longName")]
+    pub(crate) long_name: String,
+    pub(crate) displayname: String,
+    pub(crate) alternatename: String,
+    #[serde(rename = "canViewTimetable")]
+    pub(crate) can_view_timetable: bool,
+    #[serde(rename = "roomCapacity")]
+    pub(crate) room_capacity: u64,
+}
+
+impl From<&OriginalRoom> for OriginalRoom {
+    fn from(val: &OriginalRoom) -> Self {
+        Self {
+            id: val.id,
+            name: String::from(&val.name),
+            long_name: String::from(&val.long_name),
+            displayname: String::from(&val.displayname),
+            alternatename: String::from(&val.alternatename),
+            can_view_timetable: val.can_view_timetable,
+            room_capacity: val.room_capacity,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Room {
+    pub(crate) id: u64,
+    #[serde(rename = "orgId")]
+    pub(crate) original_room_id: u64,
+    pub(crate) original_room: Option<OriginalRoom>,
+    pub(crate) missing: bool,
+    pub(crate) state: ElementState,
+    pub(crate) name: String,
+    #[serde(rename = "longName")]
+    pub(crate) long_name: String,
+    pub(crate) displayname: String,
+    pub(crate) alternatename: String,
+    #[serde(rename = "canViewTimetable")]
+    pub(crate) can_view_timetable: bool,
+    #[serde(rename = "roomCapacity")]
+    pub(crate) room_capacity: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct OriginalTeacher {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    #[serde(rename = "canViewTimetable")]
+    pub(crate) can_view_timetable: bool,
+    #[serde(rename = "externKey")]
+    pub(crate) extern_key: String,
+    #[serde(rename = "roomCapacity")]
+    pub(crate) room_capacity: u64,
+}
+
+impl From<&OriginalTeacher> for OriginalTeacher {
+    fn from(val: &OriginalTeacher) -> Self {
+        Self {
+            id: val.id,
+            name: val.name.to_string(),
+            can_view_timetable: val.can_view_timetable,
+            extern_key: val.extern_key.to_string(),
+            room_capacity: val.room_capacity,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Teacher {
+    pub(crate) id: u64,
+    #[serde(rename = "orgId")]
+    pub(crate) original_teacher_id: u64,
+    pub(crate) original_teacher: Option<OriginalTeacher>,
+    pub(crate) missing: bool,
+    pub(crate) state: ElementState,
+    pub(crate) name: String,
+    #[serde(rename = "canViewTimetable")]
+    pub(crate) can_view_timetable: bool,
+    #[serde(rename = "externKey")]
+    pub(crate) extern_key: String,
+    #[serde(rename = "roomCapacity")]
+    pub(crate) room_capacity: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct OriginalSubject {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    #[serde(rename = "longName")]
+    pub(crate) long_name: String,
+    #[serde(rename = "displayname")]
+    pub(crate) display_name: String,
+    #[serde(rename = "alternatename")]
+    pub(crate) alternate_name: String,
+    #[serde(rename = "backColor")]
+    pub(crate) back_color: String,
+    #[serde(rename = "canViewTimetable")]
+    pub(crate) can_view_timetable: bool,
+    #[serde(rename = "roomCapacity")]
+    pub(crate) room_capacity: u64,
+    #[serde(rename = "foreColor")]
+    pub(crate) fore_color: Option<String>,
+}
+
+impl From<&OriginalSubject> for OriginalSubject {
+    fn from(val: &OriginalSubject) -> Self {
+        OriginalSubject {
+            id: val.id,
+            name: String::from(&val.name),
+            long_name: String::from(&val.long_name),
+            display_name: String::from(&val.display_name),
+            alternate_name: String::from(&val.alternate_name),
+            back_color: String::from(&val.back_color),
+            can_view_timetable: val.can_view_timetable,
+            room_capacity: val.room_capacity,
+            fore_color: val.fore_color.as_ref().map(String::from),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Subject {
+    pub(crate) id: u64,
+    #[serde(rename = "orgId")]
+    pub(crate) original_subject_id: u64,
+    pub(crate) original_subject: Option<OriginalSubject>,
+    pub(crate) missing: bool,
+    pub(crate) state: ElementState,
+    pub(crate) name: String,
+    #[serde(rename = "longName")]
+    pub(crate) long_name: String,
+    #[serde(rename = "displayname")]
+    pub(crate) display_name: String,
+    #[serde(rename = "alternatename")]
+    pub(crate) alternate_name: String,
+    #[serde(rename = "backColor")]
+    pub(crate) back_color: String,
+    #[serde(rename = "canViewTimetable")]
+    pub(crate) can_view_timetable: bool,
+    #[serde(rename = "roomCapacity")]
+    pub(crate) room_capacity: u64,
+    #[serde(rename = "foreColor")]
+    pub(crate) fore_color: Option<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub(crate) enum PeriodState {
+    Standard,
+    Substitution,
+    RoomSubstitution,
+    Cancel,
+    Additional,
+    SubstitutionText,
+    Unknown(String),
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct Period {
+    pub(crate) id: u64,
+    #[serde(rename = "lessonId")]
+    pub(crate) lesson_id: u64,
+    #[serde(rename = "lessonCode")]
+    pub(crate) lesson_code: String,
+    #[serde(rename = "lessonText")]
+    pub(crate) lesson_text: String,
+    #[serde(rename = "periodText")]
+    pub(crate) text: String,
+    #[serde(rename = "hasPeriodText")]
+    pub(crate) has_period_text: bool,
+    #[serde(rename = "periodInfo")]
+    pub(crate) info: String,
+    #[serde(rename = "hasInfo")]
+    has_info: bool,
+    #[serde(rename = "substText")]
+    pub(crate) substitution_text: String,
+    #[serde(rename = "studentGroup")]
+    pub(crate) student_group: Option<String>,
+    pub(crate) date: chrono::NaiveDate,
+    #[serde(rename = "startTime")]
+    pub(crate) start_time: chrono::NaiveTime,
+    #[serde(rename = "endTime")]
+    pub(crate) end_time: chrono::NaiveTime,
+    #[serde(rename = "periodNumbers")]
+    pub(crate) period_numbers: Option<PeriodNumbers>,
+    pub(crate) state: PeriodState,
+    pub(crate) is: PeriodFlags,
+    pub(crate) klassen: Vec<Klasse>,
+    pub(crate) teachers: Vec<Teacher>,
+    pub(crate) subjects: Vec<Subject>,
+    pub(crate) rooms: Vec<Room>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ElementType {
+    Klasse = 1,
+    Teacher = 2,
+    Subject = 3,
+    Room = 4,
+    Student = 5,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct TimetableElement {
+    pub(crate) id: u64,
+    pub(crate) name: String,
+    #[serde(rename = "longName")]
+    pub(crate) long_name: String,
+    pub(crate) displayname: String,
+    #[serde(rename = "canViewTimetable")]
+    pub(crate) can_view_timetable: bool,
+}
+
+/// Retrieves all elements of the given type, that are visible to the logged in user
+pub(crate) async fn get_elements(
+    client: &Client,
+    session_id: &str,
+    element_type: ElementType,
+) -> anyhow::Result<Vec<TimetableElement>> {
+    let response = client
+        .get(format!(
+            "https://{}/WebUntis/api/public/timetable/weekly/pageconfig?type={}&date={}",
+            std::env::var("UNTIS_HOST").expect("'UNTIS_HOST' not defined!"),
+            element_type as u64,
+            chrono::Local::now().format("%Y-%m-%d")
+        ))
+        .header(COOKIE, format!("JSESSIONID={}", session_id))
+        .send()
+        .await?;
+    let data: serde_json::Value = response.json().await?;
+    let elements = data
+        .get("data")
+        .ok_or(anyhow!("'.data' field not present in page config"))?
+        .get("elements")
+        .ok_or(anyhow!("'.data.elements' field not present in page config"))?;
+    Ok(serde_json::from_value(elements.clone())?)
+}
+
+/// Looks up an element by its (long/display) name, ignoring elements whose timetable may not be
+/// viewed by the logged in user
+async fn find_element(
+    client: &Client,
+    session_id: &str,
+    element_type: ElementType,
+    name: &str,
+) -> anyhow::Result<Option<TimetableElement>> {
+    Ok(get_elements(client, session_id, element_type)
+        .await?
+        .into_iter()
+        .filter(|element| element.can_view_timetable)
+        .find(|element| {
+            element.name.eq_ignore_ascii_case(name)
+                || element.long_name.eq_ignore_ascii_case(name)
+                || element.displayname.eq_ignore_ascii_case(name)
+        }))
+}
+
+pub(crate) async fn get_timetable(
+    client: &Client,
+    session_id: &str,
+    element_type: ElementType,
+    element_id: u64,
+    date: chrono::NaiveDate,
+) -> Result<serde_json::Value, Error> {
+    let response = client.get(format!(
+            "https://{}/WebUntis/api/public/timetable/weekly/data?elementType={}&elementId={}&date={}&formatId=1",
+            std::env::var("UNTIS_HOST").expect("'UNTIS_HOST' not defined!"),
+            element_type as u64,
+            element_id,
+            date.format("%Y-%m-%d")
+    )).header(COOKIE, format!("JSESSIONID={}", session_id))
+        .send()
+        .await?;
+    let data: serde_json::Value = response.json().await?;
+    Ok(data)
+}
+
+/// Response of `/api/public/timetable/weekly/data`.
+///
+/// Elements and periods are kept as raw values here, so that every single one of them can be
+/// deserialized (and, in lenient mode, skipped) on its own.
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct WeeklyTimetableResponse {
+    pub(crate) data: WeeklyTimetableEnvelope,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct WeeklyTimetableEnvelope {
+    pub(crate) result: WeeklyTimetableResult,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct WeeklyTimetableResult {
+    pub(crate) data: WeeklyTimetableData,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct WeeklyTimetableData {
+    pub(crate) elements: Vec<serde_json::Value>,
+    #[serde(rename = "elementPeriods")]
+    pub(crate) element_periods: HashMap<String, Vec<serde_json::Value>>,
+}
+
+/// Only the fields shared by all element types. The full element is deserialized into the model
+/// matching its type afterwards.
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct WeeklyElementHeader {
+    #[serde(rename = "type")]
+    pub(crate) element_type: u64,
+    pub(crate) id: u64,
+}
+
+/// An element reference inside of a period
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct WeeklyPeriodElement {
+    #[serde(rename = "type")]
+    pub(crate) element_type: u64,
+    pub(crate) id: u64,
+    #[serde(rename = "orgId")]
+    pub(crate) original_id: u64,
+    pub(crate) missing: bool,
+    pub(crate) state: String,
+    #[serde(rename = "backColor")]
+    pub(crate) back_color: Option<String>,
+    #[serde(rename = "foreColor")]
+    pub(crate) fore_color: Option<String>,
+}
+
+/// The flags WebUntis sets on a period. Only flags, that apply, are sent.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(crate = "rocket::serde", default)]
+pub(crate) struct PeriodFlags {
+    pub(crate) standard: bool,
+    pub(crate) substitution: bool,
+    #[serde(rename = "roomSubstitution")]
+    pub(crate) room_substitution: bool,
+    pub(crate) cancelled: bool,
+    pub(crate) additional: bool,
+    pub(crate) free: bool,
+    pub(crate) exam: bool,
+    pub(crate) event: bool,
+    #[serde(rename = "officeHour")]
+    pub(crate) office_hour: bool,
+    pub(crate) shift: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+struct WeeklyPeriod {
+    pub(crate) id: u64,
+    #[serde(rename = "lessonId", default)]
+    pub(crate) lesson_id: u64,
+    #[serde(rename = "lessonCode", default)]
+    pub(crate) lesson_code: String,
+    #[serde(rename = "lessonText")]
+    pub(crate) lesson_text: Option<String>,
+    #[serde(rename = "periodText")]
+    period_text: Option<String>,
+    #[serde(rename = "hasPeriodText", default)]
+    pub(crate) has_period_text: bool,
+    #[serde(rename = "periodInfo")]
+    period_info: Option<String>,
+    #[serde(rename = "substText")]
+    pub(crate) substitution_text: Option<String>,
+    #[serde(rename = "hasInfo", default)]
+    has_info: bool,
+    #[serde(rename = "studentGroup")]
+    pub(crate) student_group: Option<String>,
+    pub(crate) date: u64,
+    #[serde(rename = "startTime")]
+    pub(crate) start_time: u64,
+    #[serde(rename = "endTime")]
+    pub(crate) end_time: u64,
+    pub(crate) elements: Vec<WeeklyPeriodElement>,
+    #[serde(rename = "cellState")]
+    pub(crate) cell_state: String,
+    #[serde(default)]
+    pub(crate) is: PeriodFlags,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ParseMode {
+    /// Fail on the first unexpected value in the timetable
+    Strict,
+    /// Map unknown values to `Unknown` variants and skip malformed periods
+    Lenient,
+}
+
+/// The parse mode used by the endpoints, configured through `UNTIS_PARSE_MODE` (defaults to
+/// lenient parsing)
+pub(crate) fn parse_mode() -> ParseMode {
+    match std::env::var("UNTIS_PARSE_MODE") {
+        Ok(mode) if mode.eq_ignore_ascii_case("strict") => ParseMode::Strict,
+        _ => ParseMode::Lenient,
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub(crate) struct ParseWarning {
+    pub(crate) path: String,
+    pub(crate) message: String,
+}
+
+struct Diagnostics {
+    pub(crate) mode: ParseMode,
+    pub(crate) warnings: Vec<ParseWarning>,
+}
+
+impl Diagnostics {
+    /// Records a problem at the given JSON path. In strict mode the problem is returned as an
+    /// error, in lenient mode it is only logged and collected.
+    fn report(&mut self, path: String, error: anyhow::Error) -> anyhow::Result<()> {
+        match self.mode {
+            ParseMode::Strict => Err(error.context(path)),
+            ParseMode::Lenient => {
+                warn!("Problem while parsing timetable at {path}: {error}");
+                self.warnings.push(ParseWarning {
+                    path,
+                    message: error.to_string(),
+                });
+                Ok(())
+            }
+        }
+    }
+}
+
+/// All elements of a weekly timetable by their id, used to resolve the element references of
+/// the periods
+#[derive(Default)]
+struct TimetableElements {
+    pub(crate) klassen: HashMap<u64, OriginalKlasse>,
+    pub(crate) teachers: HashMap<u64, OriginalTeacher>,
+    pub(crate) subjects: HashMap<u64, OriginalSubject>,
+    pub(crate) rooms: HashMap<u64, OriginalRoom>,
+}
+
+impl TimetableElements {
+    pub(crate) fn insert(&mut self, element: &serde_json::Value) -> anyhow::Result<()> {
+        let header = WeeklyElementHeader::deserialize(element)?;
+        match header.element_type {
+            1 => {
+                self.klassen
+                    .insert(header.id, OriginalKlasse::deserialize(element)?);
+            }
+            2 => {
+                self.teachers
+                    .insert(header.id, OriginalTeacher::deserialize(element)?);
+            }
+            3 => {
+                self.subjects
+                    .insert(header.id, OriginalSubject::deserialize(element)?);
+            }
+            4 => {
+                self.rooms
+                    .insert(header.id, OriginalRoom::deserialize(element)?);
+            }
+            element_type => error!("Unknown Type '{element_type}' on element {element:?}"),
+        };
+        Ok(())
+    }
+}
+
+pub(crate) fn parse_timetable(
+    timetable: serde_json::Value,
+    element_id: u64,
+    mode: ParseMode,
+) -> anyhow::Result<(Vec<Period>, Vec<ParseWarning>)> {
+    let mut diagnostics = Diagnostics {
+        mode,
+        warnings: vec![],
+    };
+
+    let mut data = serde_json::from_value::<WeeklyTimetableResponse>(timetable)
+        .map_err(|err| anyhow!("timetable is not in the expected format: {err}"))?
+        .data
+        .result
+        .data;
+
+    let mut elements = TimetableElements::default();
+    for (index, element) in data.elements.iter().enumerate() {
+        if let Err(err) = elements.insert(element) {
+            diagnostics.report(format!(".data.result.data.elements[{index}]"), err)?;
+        }
+    }
+
+    // Only an error in strict mode, so that the debug endpoint can still report the warnings
+    let periods = match data.element_periods.remove(&element_id.to_string()) {
+        Some(periods) => periods,
+        None => {
+            diagnostics.report(
+                format!(".data.result.data.elementPeriods.{element_id}"),
+                anyhow!("No timetable for element {element_id} found in data!"),
+            )?;
+            vec![]
+        }
+    };
+
+    let mut serialized_periods: Vec<Period> = vec![];
+
+    for (index, period) in periods.iter().enumerate() {
+        let path = format!(".data.result.data.elementPeriods.{element_id}[{index}]");
+        let period = WeeklyPeriod::deserialize(period)
+            .map_err(anyhow::Error::from)
+            .and_then(|period| period.into_period(&path, &elements, &mut diagnostics));
+        match period {
+            Ok(period) => serialized_periods.push(period),
+            Err(err) => diagnostics.report(path, err)?,
+        }
+    }
+
+    Ok((serialized_periods, diagnostics.warnings))
+}
+
+/// Unwraps a text field of a period. A missing field is only an error in strict mode, otherwise
+/// it is reported and treated as empty.
+fn period_text(
+    value: Option<String>,
+    field: &str,
+    path: &str,
+    diagnostics: &mut Diagnostics,
+) -> anyhow::Result<String> {
+    match value {
+        Some(value) => Ok(value),
+        None => {
+            diagnostics.report(
+                format!("{path}.{field}"),
+                anyhow!("field '{field}' missing on period"),
+            )?;
+            Ok(String::new())
+        }
+    }
+}
+
+impl WeeklyPeriod {
+    /// Resolves the element references of this period and converts it into a [`Period`]
+    fn into_period(
+        self,
+        path: &str,
+        elements: &TimetableElements,
+        diagnostics: &mut Diagnostics,
+    ) -> anyhow::Result<Period> {
+        let mut period_klassen: Vec<Klasse> = vec![];
+        let mut period_rooms: Vec<Room> = vec![];
+        let mut period_teachers: Vec<Teacher> = vec![];
+        let mut period_subjects: Vec<Subject> = vec![];
+
+        for (index, element) in self.elements.into_iter().enumerate() {
+            let id = element.id;
+            let original_id = element.original_id;
+            let state = match element.state.as_str() {
+                "ABSENT" => ElementState::Absent,
+                "REGULAR" => ElementState::Regular,
+                "SUBSTITUTED" => ElementState::Substituted,
+                state => {
+                    diagnostics.report(
+                        format!("{path}.elements[{index}].state"),
+                        anyhow!("Unknown type of 'state' ({state}) on element {id}"),
+                    )?;
+                    ElementState::Unknown(state.to_string())
+                }
+            };
+            match element.element_type {
+                1 => {
+                    let klasse_info = elements
+                        .klassen
+                        .get(&id)
+                        .ok_or(anyhow!("Class with id {} has not been found!", id))?;
+                    period_klassen.push(Klasse {
+                        id,
+                        original_klasse_id: original_id,
+                        original_klasse: elements.klassen.get(&original_id).map(|k| k.into()),
+                        missing: element.missing,
+                        state,
+                        name: klasse_info.name.to_string(),
+                        long_name: klasse_info.long_name.to_string(),
+                        displayname: klasse_info.displayname.to_string(),
+                        alternatename: klasse_info.alternatename.to_string(),
+                        can_view_timetable: klasse_info.can_view_timetable,
+                        room_capacity: klasse_info.room_capacity,
+                    })
+                }
+                2 => {
+                    let teacher_info = elements
+                        .teachers
+                        .get(&id)
+                        .ok_or(anyhow!("Teacher with id {} has not been found!", id))?;
+                    period_teachers.push(Teacher {
+                        id,
+                        original_teacher_id: original_id,
+                        original_teacher: elements.teachers.get(&original_id).map(|t| t.into()),
+                        state,
+                        missing: element.missing,
+                        name: teacher_info.name.to_string(),
+                        can_view_timetable: teacher_info.can_view_timetable,
+                        extern_key: teacher_info.extern_key.to_string(),
+                        room_capacity: teacher_info.room_capacity,
+                    })
+                }
+                3 => {
+                    let subject_info = elements
+                        .subjects
+                        .get(&id)
+                        .ok_or(anyhow!("Subject with id {} has not been found!", id))?;
+                    period_subjects.push(Subject {
+                        id,
+                        original_subject_id: original_id,
+                        original_subject: elements.subjects.get(&original_id).map(|t| t.into()),
+                        missing: element.missing,
+                        state,
+                        name: subject_info.name.to_string(),
+                        long_name: subject_info.long_name.to_string(),
+                        display_name: subject_info.display_name.to_string(),
+                        alternate_name: subject_info.alternate_name.to_string(),
+                        back_color: element
+                            .back_color
+                            .unwrap_or_else(|| subject_info.back_color.to_string()),
+                        can_view_timetable: subject_info.can_view_timetable,
+                        room_capacity: subject_info.room_capacity,
+                        fore_color: element.fore_color,
+                    })
+                }
+                4 => {
+                    let room_info = elements
+                        .rooms
+                        .get(&id)
+                        .ok_or(anyhow!("Room with id {} has not been found!", id))?;
+                    period_rooms.push(Room {
+                        id,
+                        original_room_id: original_id,
+                        original_room: elements.rooms.get(&original_id).map(|t| t.into()),
+                        missing: element.missing,
+                        state,
+                        name: room_info.name.to_string(),
+                        long_name: room_info.long_name.to_string(),
+                        displayname: room_info.displayname.to_string(),
+                        alternatename: room_info.alternatename.to_string(),
+                        can_view_timetable: room_info.can_view_timetable,
+                        room_capacity: room_info.room_capacity,
+                    })
+                }
+                type_ => diagnostics.report(
+                    format!("{path}.elements[{index}].type"),
+                    anyhow!("Unknown type '{type_}' of element {id}"),
+                )?,
+            };
+        }
+
+        let period_state = match self.cell_state.as_str() {
+            "CANCEL" => PeriodState::Cancel,
+            "STANDARD" => PeriodState::Standard,
+            "SUBSTITUTION" => PeriodState::Substitution,
+            "ROOMSUBSTITUTION" => PeriodState::RoomSubstitution,
+            "ADDITIONAL" => PeriodState::Additional,
+            "SUBST_TEXT" => PeriodState::SubstitutionText,
+            state => {
+                diagnostics.report(
+                    format!("{path}.cellState"),
+                    anyhow!(
+                        "Unknown type of 'cellState' ({state}) on period {}",
+                        self.id
+                    ),
+                )?;
+                PeriodState::Unknown(state.to_string())
+            }
+        };
+        Ok(Period {
+            id: self.id,
+            lesson_id: self.lesson_id,
+            lesson_code: self.lesson_code,
+            lesson_text: period_text(self.lesson_text, "lessonText", path, diagnostics)?,
+            text: period_text(self.period_text, "periodText", path, diagnostics)?,
+            has_period_text: self.has_period_text,
+            info: period_text(self.period_info, "periodInfo", path, diagnostics)?,
+            has_info: self.has_info,
+            substitution_text: period_text(self.substitution_text, "substText", path, diagnostics)?,
+            student_group: self.student_group,
+            date: untis_date(self.date)?,
+            start_time: untis_time(self.start_time)?,
+            end_time: untis_time(self.end_time)?,
+            period_numbers: None,
+            state: period_state,
+            is: self.is,
+            klassen: period_klassen,
+            teachers: period_teachers,
+            subjects: period_subjects,
+            rooms: period_rooms,
+        })
+    }
+}
+
+/// The longest break between two periods, for them to still be merged into one (e.g. a double
+/// period), configured in minutes through `UNTIS_MERGE_GAP`. `off` disables merging.
+pub(crate) fn merge_gap() -> Option<chrono::Duration> {
+    match std::env::var("UNTIS_MERGE_GAP") {
+        Ok(gap) if gap.eq_ignore_ascii_case("off") => None,
+        Ok(gap) => match gap.parse() {
+            Ok(minutes) => Some(chrono::Duration::minutes(minutes)),
+            Err(_) => {
+                error!("Invalid 'UNTIS_MERGE_GAP' {gap}, using default");
+                Some(chrono::Duration::minutes(5))
+            }
+        },
+        Err(_) => Some(chrono::Duration::minutes(5)),
+    }
+}
+
+impl Period {
+    /// Whether `next` continues this period, i.e. they describe the same lesson with the same
+    /// changes and are at most `gap` apart
+    fn continues_with(&self, next: &Period, gap: chrono::Duration) -> bool {
+        fn elements<'a, T>(
+            elements: &'a [T],
+            key: impl Fn(&'a T) -> (u64, u64, &'a ElementState),
+        ) -> Vec<(u64, u64, &'a ElementState)> {
+            elements.iter().map(key).collect()
+        }
+
+        let pause = next.start_time - self.end_time;
+        self.date == next.date
+            && pause >= chrono::Duration::zero()
+            && pause <= gap
+            && self.state == next.state
+            && self.is == next.is
+            && self.lesson_text == next.lesson_text
+            && self.text == next.text
+            && self.info == next.info
+            && self.substitution_text == next.substitution_text
+            && elements(&self.subjects, |s| (s.id, s.original_subject_id, &s.state))
+                == elements(&next.subjects, |s| (s.id, s.original_subject_id, &s.state))
+            && elements(&self.teachers, |t| (t.id, t.original_teacher_id, &t.state))
+                == elements(&next.teachers, |t| (t.id, t.original_teacher_id, &t.state))
+            && elements(&self.rooms, |r| (r.id, r.original_room_id, &r.state))
+                == elements(&next.rooms, |r| (r.id, r.original_room_id, &r.state))
+            && elements(&self.klassen, |k| (k.id, k.original_klasse_id, &k.state))
+                == elements(&next.klassen, |k| (k.id, k.original_klasse_id, &k.state))
+    }
+}
+
+/// Merges adjacent periods, that only differ in their times (e.g. both halves of a double
+/// period), into one. Expects the periods to be sorted by date and time.
+pub(crate) fn merge_periods(periods: Vec<Period>, gap: chrono::Duration) -> Vec<Period> {
+    let mut merged: Vec<Period> = Vec::with_capacity(periods.len());
+    for period in periods {
+        match merged.last_mut() {
+            Some(previous) if previous.continues_with(&period, gap) => {
+                previous.end_time = period.end_time;
+                previous.period_numbers = match (previous.period_numbers, period.period_numbers) {
+                    (Some(first), Some(last)) => Some(PeriodNumbers {
+                        first: first.first,
+                        last: last.last,
+                    }),
+                    _ => None,
+                };
+            }
+            _ => merged.push(period),
+        }
+    }
+    merged
+}
+
+/// Logs in, retrieves the timetable for the week of `date` of the requested element (or of the
+/// logged in user) and parses it. Returns `None`, if the requested element could not be found.
+pub(crate) async fn fetch_timetable(
+    user: &UsernamePassword,
+    element: Option<(ElementType, &str)>,
+    date: chrono::NaiveDate,
+    mode: ParseMode,
+) -> anyhow::Result<Option<(Vec<Period>, Vec<ParseWarning>)>> {
+    let client = build_client();
+
+    info!("Logging in as {}...", &user.username);
+    let userinfo = login(&client, &user.username, &user.password).await?;
+
+    let (element_type, element_id) = match element {
+        Some((element_type, name)) => {
+            info!("Looking up {element_type:?} '{name}'...");
+            match find_element(&client, &userinfo.session_id, element_type, name).await? {
+                Some(element) => (element_type, element.id),
+                None => {
+                    info!("{element_type:?} '{name}' not found, logging out...");
+                    logout(&client, &userinfo.session_id).await?;
+                    return Ok(None);
+                }
+            }
+        }
+        None => (ElementType::Student, userinfo.person_id),
+    };
+
+    info!("Retrieving timetable...");
+    let timetable = get_timetable(
+        &client,
+        &userinfo.session_id,
+        element_type,
+        element_id,
+        date,
+    )
+    .await?;
+    // Period numbers are nice to have, so the timetable is still returned without them
+    let timegrid = get_timegrid(&client, &userinfo.session_id)
+        .await
+        .inspect_err(|err| warn!("Could not retrieve time grid: {err}"))
+        .ok();
+    if lookup_teacher_names() {
+        // Usually not permitted for students, teachers are then called by their abbreviation
+        if let Err(err) = get_teacher_surnames(&client, &userinfo.session_id, &user.username).await
+        {
+            info!("Could not retrieve teacher names: {err}");
+        }
+    }
+    info!("Logging out...");
+    logout(&client, &userinfo.session_id).await?;
+
+    info!("Parsing timetable...");
+    let (timetable, warnings) = parse_timetable(timetable, element_id, mode)?;
+    Ok(Some((prepare_timetable(timetable, timegrid), warnings)))
+}
+
+/// Attaches the period numbers and sorts the periods
+pub(crate) fn prepare_timetable(
+    mut timetable: Vec<Period>,
+    timegrid: Option<&Timegrid>,
+) -> Vec<Period> {
+    if let Some(timegrid) = timegrid {
+        for period in timetable.iter_mut() {
+            period.period_numbers = timegrid.period_numbers(period);
+        }
+    }
+    timetable.sort_by_key(|period| chrono::NaiveDateTime::new(period.date, period.start_time));
+    timetable
+}
+
+/// Merges double periods for presentation (speech, dashboard and kiosk), unless disabled through
+/// `UNTIS_MERGE_GAP`. The raw timetable endpoints keep the periods as WebUntis returns them.
+pub(crate) fn merged_timetable(timetable: Vec<Period>) -> Vec<Period> {
+    match merge_gap() {
+        Some(gap) => merge_periods(timetable, gap),
+        None => timetable,
+    }
+}